@@ -0,0 +1,32 @@
+use chip8::asm::Assembly;
+
+/// Assembles Octo source into a program and writes a listing and symbols next to it.
+///
+/// `cargo run --example asm -- quirks.8o quirks.ch8` also writes `quirks.lst` and `quirks.sym`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = std::env::args().skip(1);
+    let (Some(source_path), Some(program_path)) = (arguments.next(), arguments.next()) else {
+        eprintln!("usage: asm <source path> <program path>");
+        std::process::exit(2);
+    };
+
+    let source = std::fs::read_to_string(&source_path)?;
+    let assembly = match Assembly::new(&source) {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("{source_path}:{e}");
+            std::process::exit(1);
+        }
+    };
+    std::fs::write(&program_path, assembly.bytes())?;
+    let listing_path = std::path::Path::new(&program_path).with_extension("lst");
+    std::fs::write(listing_path, assembly.to_listing(&source))?;
+    let source_name = std::path::Path::new(&source_path)
+        .file_name()
+        .unwrap_or_default();
+    let symbol_table = assembly.symbol_table(&source_name.to_string_lossy());
+    let symbols_path = std::path::Path::new(&program_path).with_extension("sym");
+    std::fs::write(symbols_path, symbol_table.to_text())?;
+    println!("assembled {} bytes", assembly.bytes().len());
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use chip8::disasm::{ControlFlowGraph, Syntax};
+
+/// Prints what the static analysis found in a program, or its control flow graph as Graphviz DOT.
+///
+/// `cargo run --example control_flow -- roms/vers.ch8`
+/// `cargo run --example control_flow -- roms/vers.ch8 --dot | dot -Tsvg > vers.svg`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let Some(program_path) = arguments
+        .iter()
+        .find(|argument| !argument.starts_with("--"))
+    else {
+        eprintln!("usage: control_flow <program path> [--dot]");
+        std::process::exit(2);
+    };
+
+    let graph = ControlFlowGraph::new(&std::fs::read(program_path)?);
+    if arguments.iter().any(|argument| argument == "--dot") {
+        print!("{}", graph.to_dot(Syntax::Octo));
+        return Ok(());
+    }
+
+    println!("{} basic blocks", graph.blocks().len());
+    for range in graph.unreachable_ranges() {
+        println!("unreachable bytes {:03X}..{:03X}", range.start, range.end);
+    }
+    for address in graph.indirect_jumps() {
+        println!("indirect jump at {address:03X}");
+    }
+    for write in graph.self_modifying_writes() {
+        let (start, end) = (write.target.start, write.target.end);
+        println!(
+            "write to code {start:03X}..{end:03X} at {:03X}",
+            write.address
+        );
+    }
+    for address in graph.undecodable_addresses() {
+        println!("no instruction at reachable address {address:03X}");
+    }
+    Ok(())
+}
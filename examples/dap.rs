@@ -0,0 +1,12 @@
+use chip8::dap::DapServer;
+
+/// A Debug Adapter Protocol server over stdin and stdout for editors like VS Code.
+///
+/// Configure the editor to start `cargo run --example dap` and launch with
+/// `{ "program": "game.ch8", "symbols": "game.sym", "stopOnEntry": true }`.
+/// `cargo run --example asm -- game.8o game.ch8` writes the symbols that map source lines to addresses.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::io::BufReader::new(std::io::stdin());
+    DapServer::new().run(input, std::io::stdout().lock())?;
+    Ok(())
+}
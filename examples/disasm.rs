@@ -0,0 +1,24 @@
+use chip8::disasm::{Disassembly, Syntax};
+
+/// Prints an annotated disassembly of a program.
+///
+/// `cargo run --example disasm -- roms/pong.ch8 [--cowgod]`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let Some(program_path) = arguments
+        .iter()
+        .find(|argument| !argument.starts_with("--"))
+    else {
+        eprintln!("usage: disasm <program path> [--cowgod]");
+        std::process::exit(2);
+    };
+    let syntax = if arguments.iter().any(|argument| argument == "--cowgod") {
+        Syntax::Cowgod
+    } else {
+        Syntax::Octo
+    };
+
+    let disassembly = Disassembly::new(&std::fs::read(program_path)?, syntax);
+    print!("{disassembly}");
+    Ok(())
+}
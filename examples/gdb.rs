@@ -0,0 +1,21 @@
+use chip8::gdb::GdbStub;
+use chip8::interpreter::{Debugger, Interpreter};
+
+/// Waits for a GDB Remote Serial Protocol client on localhost and debugs a program with it.
+///
+/// `cargo run --example gdb -- roms/pong.ch8 [port]`, then in gdb `set endian big` and `target remote :1234`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = std::env::args().skip(1);
+    let Some(program_path) = arguments.next() else {
+        eprintln!("usage: gdb <program path> [port]");
+        std::process::exit(2);
+    };
+    let port: u16 = arguments.next().map_or(Ok(1234), |port| port.parse())?;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.load_program_from_path(&program_path)?;
+    let mut stub = GdbStub::new(Debugger::new(interpreter));
+    println!("waiting for a client on 127.0.0.1:{port}");
+    stub.listen(("127.0.0.1", port))?;
+    Ok(())
+}
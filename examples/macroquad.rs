@@ -1,4 +1,7 @@
-use chip8::interpreter::{ConfigurationBuilder, Interpreter};
+use chip8::interpreter::{
+    AudioRecorder, ConfigurationBuilder, Debugger, Display, Interpreter, Movie, Platform,
+    RewindBudget, RewindBuffer, SaveState, StopReason, SymbolTable,
+};
 use macroquad::{
     miniquad::window::screen_size,
     prelude::*,
@@ -6,22 +9,51 @@ use macroquad::{
 };
 use std::ops::DerefMut;
 
-const DEFAULT_CHIP8: ConfigurationBuilder = Interpreter::builder().instruction_delay(std::time::Duration::ZERO);
-const HIGH_RESOLUTION_CHIP8: ConfigurationBuilder = DEFAULT_CHIP8.display_width(128).display_height(64);
+/// One quick save slot per key. Shift + key saves to a slot. The key alone loads the slot.
+const SAVE_SLOT_KEYS: [KeyCode; 4] = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+
+/// Held down to step backwards in time.
+const REWIND_KEY: KeyCode = KeyCode::Backspace;
+/// Ten seconds of frames.
+const REWIND_BUDGET: RewindBudget = RewindBudget::Frames(600);
+
+/// Restarts the loaded program and records a [Movie] of the keypad and the sound.
+const RECORD_KEY: KeyCode = KeyCode::F5;
+/// Stops recording and saves the [Movie] and a `.wav` of the sound to the `movies` directory. Replay it with `cargo run --example replay`.
+const STOP_RECORDING_KEY: KeyCode = KeyCode::F6;
+const AUDIO_SAMPLE_RATE: u32 = 44100;
+
+/// Pauses and resumes the program. Not available while recording.
+const PAUSE_KEY: KeyCode = KeyCode::F7;
+/// Step through the paused program. See [Debugger::step_into], [Debugger::step_over] and [Debugger::step_out].
+const STEP_INTO_KEY: KeyCode = KeyCode::F8;
+const STEP_OVER_KEY: KeyCode = KeyCode::F9;
+const STEP_OUT_KEY: KeyCode = KeyCode::F10;
+
+/// Colors for each color index of the display. Index 0 is the background.
+const PALETTE: [Color; 16] = [
+    WHITE, BLACK, GRAY, DARKGRAY, RED, GREEN, BLUE, YELLOW, ORANGE, PURPLE, PINK, BROWN, LIME,
+    SKYBLUE, MAROON, DARKBLUE,
+];
 
 #[macroquad::main("chip8")]
 async fn main() {
-    let mut chip8 = DEFAULT_CHIP8.build();
+    let mut debugger = Debugger::new(Interpreter::default());
+    let platform_names = Platform::ALL.map(Platform::name);
+    let mut platform_index = 0;
+    let mut loaded_program_path = None::<std::path::PathBuf>;
 
     let mut load_program_error = None::<String>;
+    let mut execution_error = None::<String>;
+    let mut save_slots: [Option<SaveState>; 4] = Default::default();
+    let mut rewind_buffer = RewindBuffer::new(REWIND_BUDGET);
+    let mut movie = None::<Movie>;
+    let mut audio_recorder = None::<AudioRecorder>;
+    // the last stop of the paused program
+    let mut pause_status = None::<String>;
 
-    let mut display_image = Image::gen_image_color(
-        chip8.configuration().display_width() as _,
-        chip8.configuration().display_height() as _,
-        WHITE,
-    );
-    let mut display_texture = Texture2D::from_image(&display_image);
-    display_texture.set_filter(FilterMode::Nearest);
+    let (mut display_image, mut display_texture) =
+        create_display_texture(debugger.interpreter().display());
 
     let programs = get_programs();
     let longest_program_name_width = programs
@@ -29,7 +61,6 @@ async fn main() {
         .max_by_key(|(_, name, _)| name.len())
         .map(|(_, _, name_dimensions)| name_dimensions.width)
         .unwrap_or_default();
-    let mut display_scale = 100.0;
 
     loop {
         clear_background(WHITE);
@@ -43,9 +74,10 @@ async fn main() {
             screen_dimensions.x - choose_window_size.x,
             screen_dimensions.y,
         );
+        let display_scale = display_image.width() as f32 * 100.0 / 64.0;
         let display_texture_size = vec2(
-            chip8.configuration().display_width() as f32 * screen_dimensions.x,
-            chip8.configuration().display_height() as f32 * screen_dimensions.y,
+            display_image.width() as f32 * screen_dimensions.x,
+            display_image.height() as f32 * screen_dimensions.y,
         ) / display_scale;
         let display_texture_position = (display_window_size - display_texture_size) / 2.0;
 
@@ -59,47 +91,108 @@ async fn main() {
                     .ui(ui);
             });
 
+        let mut program_to_load = None;
         widgets::Window::new(hash!(), choose_window_position, choose_window_size)
             .label("Choose program")
             .titlebar(true)
             .movable(false)
             .ui(root_ui().deref_mut(), |ui| {
+                let previous_platform_index = platform_index;
+                widgets::ComboBox::new(hash!(), &platform_names).ui(ui, &mut platform_index);
+                let messages = load_program_error.iter().chain(&execution_error);
+                for error in messages.chain(&pause_status) {
+                    for line in error.lines() {
+                        ui.label(None, line);
+                    }
+                }
+                program_to_load = (platform_index != previous_platform_index)
+                    .then(|| loaded_program_path.clone())
+                    .flatten();
+
                 for (i, (path, name, name_dimensions)) in programs.iter().enumerate() {
                     if widgets::Button::new(name.as_str())
                         .position(vec2(
                             (choose_window_size.x - name_dimensions.width) / 2.0,
-                            i as f32 * 24.0,
+                            (i + 1) as f32 * 24.0,
                             // i as f32 * name_dimensions.height * 2.0,
                         ))
                         .ui(ui)
                     {
-                        chip8 = if name.contains("hires") {
-                            display_scale = 200.0;
-                            HIGH_RESOLUTION_CHIP8
-                        } else {
-                            display_scale = 100.0;
-                            DEFAULT_CHIP8
-                        }
-                        .build();
-
-                        display_image = Image::gen_image_color(
-                            chip8.configuration().display_width() as _,
-                            chip8.configuration().display_height() as _,
-                            WHITE,
-                        );
-                        display_texture = Texture2D::from_image(&display_image);
-                        display_texture.set_filter(FilterMode::Nearest);
-
-                        if let Err(e) = chip8.load_program_from_path(path) {
-                            load_program_error = Some(e.to_string());
-                        }
+                        program_to_load = Some(path.clone());
                     };
                 }
             });
 
-        handle_input(chip8.keypad_mut());
+        let start_recording = is_key_pressed(RECORD_KEY) && loaded_program_path.is_some();
+        if start_recording {
+            program_to_load = loaded_program_path.clone();
+        }
+        if let Some(path) = program_to_load {
+            let mut chip8 = ConfigurationBuilder::preset(Platform::ALL[platform_index])
+                .random_seed_from_entropy()
+                .build();
+
+            load_program_error = chip8
+                .load_program_from_path(&path)
+                .err()
+                .map(|e| e.to_string());
+            // symbols written next to the program by `cargo run --example asm`
+            chip8.set_symbol_table(SymbolTable::from_path(path.with_extension("sym")).ok());
+            execution_error = None;
+            loaded_program_path = Some(path);
+            rewind_buffer.clear();
+            movie = start_recording.then(|| Movie::new(&chip8));
+            audio_recorder = start_recording.then(|| AudioRecorder::new(AUDIO_SAMPLE_RATE));
+            (display_image, display_texture) = create_display_texture(chip8.display());
+            debugger = Debugger::new(chip8);
+            pause_status = None;
+        }
+        if is_key_pressed(STOP_RECORDING_KEY) {
+            let recording = movie.take().zip(audio_recorder.take());
+            if let (Some((movie, audio_recorder)), Some(path)) = (recording, &loaded_program_path) {
+                if let Err(e) = save_movie(&movie, &audio_recorder, path) {
+                    eprintln!("could not save the movie: {e}");
+                }
+            }
+        }
+
+        handle_input(debugger.interpreter_mut().keypad_mut());
+        // rewinding or loading a state would make the movie impossible to replay
+        if movie.is_none() {
+            handle_save_slots(debugger.interpreter_mut(), &mut save_slots);
+            if is_key_pressed(PAUSE_KEY) {
+                pause_status = match pause_status {
+                    Some(_) => None,
+                    None => Some(describe_pause("paused", debugger.interpreter())),
+                };
+            }
+        }
+        // macroquad runs the loop once per rendered frame
+        if is_key_down(REWIND_KEY) && movie.is_none() {
+            let _ = rewind_buffer.rewind(debugger.interpreter_mut());
+        } else if pause_status.is_some() {
+            if let Some(stop_reason) = handle_step_keys(&mut debugger) {
+                let headline = debugger.describe_stop(&stop_reason);
+                pause_status = Some(describe_pause(&headline, debugger.interpreter()));
+            }
+        } else {
+            if let Some(movie) = &mut movie {
+                movie.record_frame(debugger.interpreter().keypad());
+            }
+            rewind_buffer.push(debugger.interpreter());
+            match debugger.run_frame() {
+                StopReason::FrameComplete | StopReason::Exited => {}
+                stop_reason => execution_error = Some(debugger.describe_stop(&stop_reason)),
+            }
+            if let Some(audio_recorder) = &mut audio_recorder {
+                audio_recorder.record_frame(debugger.interpreter());
+            }
+        }
+        let chip8 = debugger.interpreter_mut();
+        if chip8.take_resolution_change().is_some() {
+            (display_image, display_texture) = create_display_texture(chip8.display());
+        }
         update_display(chip8.display(), &mut display_image, &display_texture);
-        chip8.execute_current_instruction();
 
         next_frame().await;
     }
@@ -123,15 +216,84 @@ fn get_programs() -> Vec<(std::path::PathBuf, String, TextDimensions)> {
     programs
 }
 
-fn update_display(display: &[Box<[bool]>], display_image: &mut Image, display_texture: &Texture2D) {
-    for (y, row) in display.iter().enumerate() {
-        for (x, &pixel) in row.iter().enumerate() {
-            display_image.set_pixel(x as _, y as _, if pixel { BLACK } else { WHITE });
+fn create_display_texture(display: &Display) -> (Image, Texture2D) {
+    let display_image = Image::gen_image_color(display.width() as _, display.height() as _, WHITE);
+    let display_texture = Texture2D::from_image(&display_image);
+    display_texture.set_filter(FilterMode::Nearest);
+    (display_image, display_texture)
+}
+
+fn update_display(display: &Display, display_image: &mut Image, display_texture: &Texture2D) {
+    for (y, row) in display.rows().enumerate() {
+        for (x, &color_index) in row.iter().enumerate() {
+            display_image.set_pixel(x as _, y as _, PALETTE[color_index as usize]);
         }
     }
     display_texture.update(display_image);
 }
 
+/// Writes `movies/<program name>.ch8m` and `movies/<program name>.wav`.
+fn save_movie(
+    movie: &Movie,
+    audio_recorder: &AudioRecorder,
+    program_path: &std::path::Path,
+) -> Result<(), std::io::Error> {
+    let program_name = program_path.file_stem().unwrap_or_default();
+    let movie_path = std::path::Path::new("movies")
+        .join(program_name)
+        .with_extension("ch8m");
+    std::fs::create_dir_all("movies")?;
+    std::fs::write(&movie_path, movie.to_bytes())?;
+    audio_recorder.write_wav(movie_path.with_extension("wav"))
+}
+
+fn handle_save_slots(chip8: &mut Interpreter, save_slots: &mut [Option<SaveState>; 4]) {
+    let is_shift_down = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+    for (key, save_slot) in SAVE_SLOT_KEYS.into_iter().zip(save_slots) {
+        if !is_key_pressed(key) {
+            continue;
+        }
+        if is_shift_down {
+            *save_slot = Some(chip8.save_state());
+        } else if let Some(save_state) = save_slot {
+            // a state saved for a different program is ignored
+            let _ = chip8.load_state(save_state);
+        }
+    }
+}
+
+/// Returns `headline` followed by the next instruction, the registers and the call stack.
+fn describe_pause(headline: &str, chip8: &Interpreter) -> String {
+    let registers = chip8.registers().map(|value| format!("{value:02X}"));
+    let call_stack: Vec<_> = chip8
+        .call_stack()
+        .iter()
+        .map(|address| format!("{address:#05X}"))
+        .collect();
+    format!(
+        "{headline}\n{}\nV0-V7 {}\nV8-VF {}\nI {:#05X}  DT {}  ST {}\nstack [{}]",
+        chip8.trace_line(),
+        registers[..8].join(" "),
+        registers[8..].join(" "),
+        chip8.address_register(),
+        chip8.delay_timer(),
+        chip8.sound_timer(),
+        call_stack.join(", "),
+    )
+}
+
+fn handle_step_keys(debugger: &mut Debugger) -> Option<StopReason> {
+    if is_key_pressed(STEP_INTO_KEY) {
+        Some(debugger.step_into())
+    } else if is_key_pressed(STEP_OVER_KEY) {
+        Some(debugger.step_over())
+    } else if is_key_pressed(STEP_OUT_KEY) {
+        Some(debugger.step_out())
+    } else {
+        None
+    }
+}
+
 fn handle_input(keypad: &mut [bool; 16]) {
     keypad[0x0] = is_key_down(KeyCode::Key1);
     keypad[0x1] = is_key_down(KeyCode::Key2);
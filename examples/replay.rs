@@ -0,0 +1,26 @@
+use chip8::interpreter::Movie;
+
+/// Replays a movie recorded by the macroquad example and prints the last frame.
+///
+/// `cargo run --example replay -- movies/pong.ch8m roms/pong.ch8`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = std::env::args().skip(1);
+    let (Some(movie_path), Some(program_path)) = (arguments.next(), arguments.next()) else {
+        eprintln!("usage: replay <movie path> <program path>");
+        std::process::exit(2);
+    };
+
+    let movie = Movie::from_bytes(&std::fs::read(movie_path)?)?;
+    let mut replay = movie.replay(std::fs::read(program_path)?)?;
+    replay.run_to_end()?;
+
+    for row in replay.interpreter().display().rows() {
+        let row: String = row
+            .iter()
+            .map(|&color_index| if color_index == 0 { ' ' } else { '█' })
+            .collect();
+        println!("{row}");
+    }
+    println!("replayed {} frames", replay.frame());
+    Ok(())
+}
@@ -3,12 +3,9 @@ use proc_macro2::Span;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput, Ident as Identifier};
 
-#[proc_macro]
-pub fn generate_terminal_tests(input: TokenStream) -> TokenStream {
-    if !input.is_empty() {
-        panic!("generate_terminal_tests takes no arguments!");
-    }
-    let tests = std::fs::read_dir("roms")
+/// Returns the test name, path and interpreter declaration of every program in `roms`.
+fn included_programs() -> Vec<(Identifier, String, proc_macro2::TokenStream)> {
+    std::fs::read_dir("roms")
         .unwrap()
         .map(|entry| {
             let entry = entry?;
@@ -16,7 +13,13 @@ pub fn generate_terminal_tests(input: TokenStream) -> TokenStream {
             test_name.truncate(test_name.len() - ".ch8".len());
 
             let interpreter_declaration = if test_name.contains("hires") {
-                quote! { Interpreter::from(Interpreter::builder().display_width(128).display_height(64).build()) }
+                quote! { Interpreter::builder().initial_resolution(Resolution::High).build() }
+            } else if test_name == "mastermind" {
+                // written for the COSMAC VIP `Bnnn`, which jumps to `nnn` plus `V0`
+                quote! { Interpreter::builder().use_variable_offset(false).build() }
+            } else if matches!(test_name.as_str(), "clock" | "life" | "space_intercept") {
+                // written for the COSMAC VIP `Fx0A`, which waits for the key to be released
+                quote! { Interpreter::builder().wait_for_key_release(true).build() }
             } else {
                 quote! { Interpreter::default() }
             };
@@ -25,21 +28,97 @@ pub fn generate_terminal_tests(input: TokenStream) -> TokenStream {
 
             let path = entry.path().to_string_lossy().to_string();
 
-            Ok(quote! {
-                    #[test]
-                    fn #test_name() {{
-                        const PATH: &str = #path;
-                        let mut interpreter = #interpreter_declaration;
-                        interpreter.load_program_from_path(PATH).unwrap();
-                        execute_program_terminal(interpreter).unwrap();
-                    }}
-            })
+            Ok((test_name, path, interpreter_declaration))
         })
         .collect::<Result<Vec<_>, std::io::Error>>()
-        .unwrap();
+        .unwrap()
+}
+
+/// Generates an interactive test for every program in `roms` that runs it in the terminal.
+/// The tests are ignored so they only run when asked for by name.
+#[proc_macro]
+pub fn generate_terminal_tests(input: TokenStream) -> TokenStream {
+    if !input.is_empty() {
+        panic!("generate_terminal_tests takes no arguments!");
+    }
+    let tests =
+        included_programs()
+            .into_iter()
+            .map(|(test_name, path, interpreter_declaration)| {
+                quote! {
+                        #[test]
+                        #[ignore = "interactive"]
+                        fn #test_name() {{
+                            const PATH: &str = #path;
+                            let mut interpreter = #interpreter_declaration;
+                            interpreter.load_program_from_path(PATH).unwrap();
+                            execute_program_terminal(interpreter).unwrap();
+                        }}
+                }
+            });
+    quote! { #(#tests)* }.into()
+}
+
+/// Generates a test for every program in `roms` that runs it without a terminal
+/// and compares the final display against its golden.
+#[proc_macro]
+pub fn generate_headless_tests(input: TokenStream) -> TokenStream {
+    if !input.is_empty() {
+        panic!("generate_headless_tests takes no arguments!");
+    }
+    let tests =
+        included_programs()
+            .into_iter()
+            .map(|(test_name, path, interpreter_declaration)| {
+                let name = test_name.to_string();
+                quote! {
+                        #[test]
+                        fn #test_name() {{
+                            const PATH: &str = #path;
+                            let interpreter = #interpreter_declaration;
+                            assert_program_matches_golden(interpreter, #name, PATH);
+                        }}
+                }
+            });
     quote! { #(#tests)* }.into()
 }
 
+/// Compares a `&Display` with the text snapshot at a path and panics on a mismatch.
+///
+/// `assert_display_matches!(interpreter.display(), "tests/golden/pong.txt")`
+///
+/// Relative paths start at the directory of the crate being tested.
+/// See `chip8::interpreter::check_display_snapshot` for how snapshots are written and blessed.
+#[proc_macro]
+pub fn assert_display_matches(input: TokenStream) -> TokenStream {
+    let input = proc_macro2::TokenStream::from(input);
+    let mut arguments = vec![proc_macro2::TokenStream::new()];
+    for token in input {
+        match &token {
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                arguments.push(proc_macro2::TokenStream::new())
+            }
+            _ => arguments.last_mut().unwrap().extend([token]),
+        }
+    }
+    if arguments.last().is_some_and(|argument| argument.is_empty()) {
+        arguments.pop();
+    }
+    let [display, path] = arguments.as_slice() else {
+        panic!("assert_display_matches takes a display and a snapshot path!");
+    };
+
+    quote! {
+        if let Err(message) = ::chip8::interpreter::check_display_snapshot(
+            #display,
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(#path),
+        ) {
+            panic!("{}", message);
+        }
+    }
+    .into()
+}
+
 /// Derives getter methods for each field using the field name. Each getter will return a reference to the field
 #[proc_macro_derive(Getters)]
 pub fn generate_getters(input: TokenStream) -> TokenStream {
@@ -0,0 +1,865 @@
+use crate::instruction::Instruction;
+use crate::interpreter::builder::{DEFAULT_PROGRAM_START, XO_CHIP_MEMORY_SIZE};
+use crate::interpreter::SymbolTable;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// An error in the source text given to [Assembly::new]. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+impl std::fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+impl std::error::Error for AssemblyError {}
+
+/// The bytes assembled from one statement of the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListingEntry {
+    pub address: u16,
+    pub length: u16,
+    /// The source line of the statement, starting at 1.
+    pub line: usize,
+}
+
+/// A program assembled from the Octo syntax that [Syntax::Octo](crate::disasm::Syntax::Octo) disassembles to.
+///
+/// Besides instructions the source can hold:
+/// - `: name` to define a label, which can be used before it is defined
+/// - `:const name value` and `:alias name vX`
+/// - `:call address` for calls to addresses without a label
+/// - numbers as data bytes: `12`, `0xFF`, `0b10100101` or `-1`
+/// - sprite rows as pixels in quotes: `"..####.."` (`#` and `1` are set pixels)
+/// - comments from `#` to the end of the line
+///
+/// ```
+/// use chip8::asm::Assembly;
+/// let assembly = Assembly::new(": main\n  v0 := 5\n  jump main").unwrap();
+/// assert_eq!(assembly.bytes(), [0x60, 0x05, 0x12, 0x00]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    program_start: u16,
+    bytes: Vec<u8>,
+    labels: BTreeMap<String, u16>,
+    listing: Vec<ListingEntry>,
+}
+
+// initialization
+impl Assembly {
+    /// Assembles a program that is loaded at [DEFAULT_PROGRAM_START].
+    pub fn new(source: &str) -> Result<Self, AssemblyError> {
+        Self::with_program_start(source, DEFAULT_PROGRAM_START as u16)
+    }
+
+    pub fn with_program_start(source: &str, program_start: u16) -> Result<Self, AssemblyError> {
+        let mut assembler = Assembler {
+            tokens: tokenize(source)?,
+            position: 0,
+            program_start,
+            bytes: Vec::new(),
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            listing: Vec::new(),
+        };
+        assembler.assemble()?;
+        Ok(Self {
+            program_start,
+            bytes: assembler.bytes,
+            labels: assembler
+                .labels
+                .into_iter()
+                .map(|(name, (address, _))| (name, address))
+                .collect(),
+            listing: assembler.listing,
+        })
+    }
+}
+
+// accessors
+impl Assembly {
+    pub const fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    /// The program for [Interpreter::load_program_from_bytes](crate::Interpreter::load_program_from_bytes).
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Returns the address of the label `name`.
+    pub fn label(&self, name: &str) -> Option<u16> {
+        self.labels.get(name).copied()
+    }
+
+    pub const fn labels(&self) -> &BTreeMap<String, u16> {
+        &self.labels
+    }
+
+    /// The address of every statement that produced bytes, in source order.
+    pub fn listing(&self) -> &[ListingEntry] {
+        &self.listing
+    }
+
+    /// Returns the source line that produced the byte at `address`.
+    pub fn line(&self, address: u16) -> Option<usize> {
+        self.listing
+            .iter()
+            .find(|entry| {
+                // the last entry can end at 0x10000
+                let end = entry.address as usize + entry.length as usize;
+                (entry.address as usize..end).contains(&(address as usize))
+            })
+            .map(|entry| entry.line)
+    }
+
+    /// Returns the labels and the source line of every statement for [Interpreter::set_symbol_table](crate::Interpreter::set_symbol_table).
+    ///
+    /// `file` is the name shown next to line numbers, usually the name of the source file.
+    pub fn symbol_table(&self, file: &str) -> SymbolTable {
+        let mut symbol_table = SymbolTable::new();
+        for (name, &address) in &self.labels {
+            symbol_table.insert_label(address, name.as_str());
+        }
+        for entry in &self.listing {
+            symbol_table.insert_line(entry.address, file, entry.line);
+        }
+        symbol_table
+    }
+
+    /// Writes `source` with the address and bytes of every line in front of it.
+    /// `source` must be the text this was assembled from.
+    /// ```text
+    /// 200  6005      v0 := 5
+    /// ```
+    pub fn to_listing(&self, source: &str) -> String {
+        let mut listing = String::new();
+        for (index, source_line) in source.lines().enumerate() {
+            let entries = self.listing.iter().filter(|entry| entry.line == index + 1);
+            let mut address = None;
+            let mut hex = String::new();
+            for entry in entries {
+                address.get_or_insert(entry.address);
+                let start = (entry.address - self.program_start) as usize;
+                for byte in &self.bytes[start..start + entry.length as usize] {
+                    let _ = write!(hex, "{byte:02X}");
+                }
+            }
+            let address = address.map_or(String::new(), |address| format!("{address:03X}"));
+            let _ = writeln!(listing, "{address:3}  {hex:8}  {source_line}");
+        }
+        listing
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// Splits `source` at whitespace and drops comments. Quoted sprite rows are a single token.
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, AssemblyError> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let mut characters = line.char_indices().peekable();
+        let mut column = 0;
+        while let Some(&(start, character)) = characters.peek() {
+            column += 1;
+            if character.is_whitespace() {
+                characters.next();
+                continue;
+            }
+            if character == '#' {
+                break;
+            }
+
+            let token_column = column;
+            let mut end = start;
+            if character == '"' {
+                characters.next();
+                let Some((closing, _)) = characters.find(|&(_, character)| character == '"') else {
+                    return Err(AssemblyError {
+                        line: line_index + 1,
+                        column: token_column,
+                        message: "the sprite row has no closing quote".to_owned(),
+                    });
+                };
+                end = closing + 1;
+            } else {
+                while let Some(&(index, character)) = characters.peek() {
+                    if character.is_whitespace() {
+                        break;
+                    }
+                    end = index + character.len_utf8();
+                    characters.next();
+                }
+            }
+            column += line[start..end].chars().count() - 1;
+            tokens.push(Token {
+                text: &line[start..end],
+                line: line_index + 1,
+                column: token_column,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// A label operand that is filled in once every label is known.
+#[derive(Debug, Clone)]
+struct Fixup {
+    /// The offset of the opcode in the program.
+    offset: usize,
+    name: String,
+    line: usize,
+    column: usize,
+    /// Whether the label is the 16 bit address after an `F000` instead of the last 12 bits of the opcode.
+    is_long: bool,
+}
+
+#[derive(Debug)]
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    /// The index of the next token.
+    position: usize,
+    program_start: u16,
+    bytes: Vec<u8>,
+    /// The address and the defining token of every label.
+    labels: BTreeMap<String, (u16, Token<'a>)>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<Fixup>,
+    listing: Vec<ListingEntry>,
+}
+
+/// An operand that is either known or a label that may not be defined yet.
+enum Address<'a> {
+    Value(u16),
+    Label(Token<'a>),
+}
+
+impl<'a> Assembler<'a> {
+    fn assemble(&mut self) -> Result<(), AssemblyError> {
+        while self.position < self.tokens.len() {
+            let start = self.bytes.len();
+            let token = self.tokens[self.position];
+            self.statement()?;
+            if self.program_start as usize + self.bytes.len() > XO_CHIP_MEMORY_SIZE {
+                return Err(error(token, "the program does not fit in memory"));
+            }
+            if self.bytes.len() > start {
+                self.listing.push(ListingEntry {
+                    address: self.program_start + start as u16,
+                    length: (self.bytes.len() - start) as u16,
+                    line: token.line,
+                });
+            }
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&(address, _)) = self.labels.get(&fixup.name) else {
+                return Err(AssemblyError {
+                    line: fixup.line,
+                    column: fixup.column,
+                    message: format!("the label `{}` is not defined", fixup.name),
+                });
+            };
+            if fixup.is_long {
+                self.bytes[fixup.offset + 2..fixup.offset + 4]
+                    .copy_from_slice(&address.to_be_bytes());
+            } else if address > 0xFFF {
+                return Err(AssemblyError {
+                    line: fixup.line,
+                    column: fixup.column,
+                    message: format!(
+                        "the label `{}` at {address:#06X} is out of range, use `i := long`",
+                        fixup.name
+                    ),
+                });
+            } else {
+                self.bytes[fixup.offset] |= (address >> 8) as u8;
+                self.bytes[fixup.offset + 1] = address as u8;
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AssemblyError> {
+        use Instruction::*;
+        let token = self.next()?;
+        match token.text {
+            ":" => {
+                let name = self.identifier()?;
+                let address = u16::try_from(self.program_start as usize + self.bytes.len())
+                    .map_err(|_| error(name, "the label is past the end of memory"))?;
+                if let Some((_, previous)) = self.labels.get(name.text) {
+                    return Err(error(
+                        name,
+                        format!(
+                            "the label `{}` is already defined on line {}",
+                            name.text, previous.line
+                        ),
+                    ));
+                }
+                self.labels.insert(name.text.to_owned(), (address, name));
+            }
+            ":const" => {
+                let name = self.identifier()?;
+                let value = self.value()?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.identifier()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":call" => self.emit_address(|nnn| CallSubroutine { nnn })?,
+            "clear" => self.emit(ClearDisplay),
+            "return" => self.emit(ReturnSubroutine),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(ScrollDown { n });
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(ScrollUp { n });
+            }
+            "scroll-right" => self.emit(ScrollRight),
+            "scroll-left" => self.emit(ScrollLeft),
+            "exit" => self.emit(Exit),
+            "lores" => self.emit(LowResolution),
+            "hires" => self.emit(HighResolution),
+            "native" => self.emit_address(|nnn| AssemblyRoutine { nnn })?,
+            "jump" => self.emit_address(|nnn| Jump { nnn })?,
+            "jump0" => self.emit_address(|nnn| JumpOffset {
+                x: (nnn >> 8) as u8,
+                nnn,
+            })?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(DrawSprite { x, y, n });
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let is_range = self.peek().is_some_and(|token| token.text == "-");
+                let is_save = token.text == "save";
+                let instruction = if is_range {
+                    self.next()?;
+                    let y = self.register()?;
+                    if is_save {
+                        StoreRegisterRange { x, y }
+                    } else {
+                        LoadRegisterRange { x, y }
+                    }
+                } else if is_save {
+                    StoreVariableRegisters { x }
+                } else {
+                    LoadVariableRegisters { x }
+                };
+                self.emit(instruction);
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(StoreFlagRegisters { x });
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(LoadFlagRegisters { x });
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(StoreBinaryCodedDecimalAddress { x });
+            }
+            "audio" => self.emit(StoreAudioPattern),
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(SelectPlanes { n });
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(match token.text {
+                    "delay" => DelayTimerAssign { x },
+                    "buzzer" => SoundTimerAssign { x },
+                    _ => PitchAssign { x },
+                });
+            }
+            "i" => self.address_register_statement()?,
+            "if" => self.if_statement()?,
+            _ if token.text.starts_with('"') => self.sprite_row(token)?,
+            _ if self.is_register(token.text) => self.register_statement(token)?,
+            _ if self.is_value(token.text) => {
+                let byte = self.byte(token)?;
+                self.bytes.push(byte);
+            }
+            _ if is_identifier(token.text) => {
+                self.position -= 1;
+                self.emit_address(|nnn| CallSubroutine { nnn })?;
+            }
+            _ => return Err(error(token, format!("unexpected `{}`", token.text))),
+        }
+        Ok(())
+    }
+
+    /// `i := address`, `i := long address`, `i := hex vX`, `i := bighex vX` and `i += vX`.
+    fn address_register_statement(&mut self) -> Result<(), AssemblyError> {
+        use Instruction::*;
+        let operator = self.next()?;
+        match operator.text {
+            "+=" => {
+                let x = self.register()?;
+                self.emit(AddressRegisterAddAssign { x });
+            }
+            ":=" => match self.peek().map(|token| token.text) {
+                Some("long") => {
+                    self.next()?;
+                    let offset = self.bytes.len();
+                    self.emit(AddressRegisterAssignLong);
+                    self.bytes.extend_from_slice(&[0, 0]);
+                    match self.address()? {
+                        Address::Value(address) => {
+                            self.bytes[offset + 2..].copy_from_slice(&address.to_be_bytes())
+                        }
+                        Address::Label(label) => self.fixups.push(Fixup {
+                            offset,
+                            name: label.text.to_owned(),
+                            line: label.line,
+                            column: label.column,
+                            is_long: true,
+                        }),
+                    }
+                }
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(AddressRegisterAssignCharacterAddress { x });
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(AddressRegisterAssignBigCharacterAddress { x });
+                }
+                _ => self.emit_address(|nnn| AddressRegisterAssign { nnn })?,
+            },
+            _ => return Err(error(operator, "expected `:=` or `+=` after `i`")),
+        }
+        Ok(())
+    }
+
+    /// `if <condition> then`, which skips the next instruction when the condition is false.
+    fn if_statement(&mut self) -> Result<(), AssemblyError> {
+        use Instruction::*;
+        let x = self.register()?;
+        let operator = self.next()?;
+        let instruction = match operator.text {
+            "key" => SkipOnKeyNotPressed { x },
+            "-key" => SkipOnKeyPressed { x },
+            "==" | "!=" => {
+                let operand = self.next()?;
+                let is_equal = operator.text == "==";
+                if self.is_register(operand.text) {
+                    let y = self.register_of(operand)?;
+                    if is_equal {
+                        SkipIfNotEqual { x, y }
+                    } else {
+                        SkipIfEqual { x, y }
+                    }
+                } else {
+                    let kk = self.byte(operand)?;
+                    if is_equal {
+                        SkipIfNotEqualValue { x, kk }
+                    } else {
+                        SkipIfEqualValue { x, kk }
+                    }
+                }
+            }
+            _ => return Err(error(operator, "expected `==`, `!=`, `key` or `-key`")),
+        };
+        self.expect("then")?;
+        self.emit(instruction);
+        Ok(())
+    }
+
+    /// `vX := ...`, `vX += ...` and the other register operators.
+    fn register_statement(&mut self, register: Token<'a>) -> Result<(), AssemblyError> {
+        use Instruction::*;
+        let x = self.register_of(register)?;
+        let operator = self.next()?;
+        let operand = self.next()?;
+        let y = if self.is_register(operand.text) {
+            Some(self.register_of(operand)?)
+        } else {
+            None
+        };
+
+        let instruction = match (operator.text, y) {
+            (":=", Some(y)) => Assign { x, y },
+            (":=", None) => match operand.text {
+                "random" => {
+                    let kk = self.byte(self.peek_token()?)?;
+                    self.next()?;
+                    RandomNumberAssign { x, kk }
+                }
+                "delay" => StoreDelayTimer { x },
+                "key" => WaitForKeyPress { x },
+                _ => AssignValue {
+                    x,
+                    kk: self.byte(operand)?,
+                },
+            },
+            ("+=", Some(y)) => AddAssign { x, y },
+            ("+=", None) => AddAssignValue {
+                x,
+                kk: self.byte(operand)?,
+            },
+            ("-=", Some(y)) => SubAssign { x, y },
+            ("-=", None) => AddAssignValue {
+                x,
+                kk: self.byte(operand)?.wrapping_neg(),
+            },
+            ("|=", Some(y)) => BitwiseOrAssign { x, y },
+            ("&=", Some(y)) => BitwiseAndAssign { x, y },
+            ("^=", Some(y)) => BitwiseXorAssign { x, y },
+            (">>=", Some(y)) => RightShiftAssign { x, y },
+            ("<<=", Some(y)) => LeftShiftAssign { x, y },
+            ("=-", Some(y)) => SubAssignSwapped { x, y },
+            ("|=" | "&=" | "^=" | ">>=" | "<<=" | "=-", None) => {
+                return Err(error(operand, "expected a register"));
+            }
+            _ => {
+                return Err(error(
+                    operator,
+                    format!("unknown operator `{}`", operator.text),
+                ))
+            }
+        };
+        self.emit(instruction);
+        Ok(())
+    }
+
+    /// `"..####.."` is one byte per 8 pixels.
+    fn sprite_row(&mut self, token: Token<'a>) -> Result<(), AssemblyError> {
+        let pixels = &token.text[1..token.text.len() - 1];
+        if pixels.is_empty() || !pixels.chars().count().is_multiple_of(8) {
+            return Err(error(
+                token,
+                "a sprite row must be a multiple of 8 pixels wide",
+            ));
+        }
+        let mut byte = 0;
+        for (index, pixel) in pixels.chars().enumerate() {
+            let is_set = match pixel {
+                '#' | '1' => true,
+                '.' | '0' | ' ' => false,
+                _ => {
+                    return Err(error(
+                        token,
+                        format!("`{pixel}` is not a pixel, use `#` or `.`"),
+                    ))
+                }
+            };
+            byte = byte << 1 | is_set as u8;
+            if index % 8 == 7 {
+                self.bytes.push(byte);
+                byte = 0;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.bytes
+            .extend_from_slice(&instruction.encode().to_be_bytes());
+    }
+
+    /// Emits an instruction with a 12 bit address operand.
+    fn emit_address(
+        &mut self,
+        instruction: impl Fn(u16) -> Instruction,
+    ) -> Result<(), AssemblyError> {
+        match self.address()? {
+            Address::Value(address) if address > 0xFFF => {
+                let token = self.tokens[self.position - 1];
+                Err(error(
+                    token,
+                    format!("the address {address:#06X} does not fit in 12 bits"),
+                ))
+            }
+            Address::Value(address) => {
+                self.emit(instruction(address));
+                Ok(())
+            }
+            Address::Label(label) => {
+                self.fixups.push(Fixup {
+                    offset: self.bytes.len(),
+                    name: label.text.to_owned(),
+                    line: label.line,
+                    column: label.column,
+                    is_long: false,
+                });
+                self.emit(instruction(0));
+                Ok(())
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AssemblyError> {
+        let token = self.peek_token()?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    /// Returns the next token without consuming it or an error at the end of the source.
+    fn peek_token(&self) -> Result<Token<'a>, AssemblyError> {
+        self.peek().ok_or_else(|| {
+            let (line, column) = self.tokens.last().map_or((1, 1), |token| {
+                (token.line, token.column + token.text.chars().count())
+            });
+            AssemblyError {
+                line,
+                column,
+                message: "unexpected end of the source".to_owned(),
+            }
+        })
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssemblyError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(error(
+                token,
+                format!("expected `{text}` but found `{}`", token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<Token<'a>, AssemblyError> {
+        let token = self.next()?;
+        if !is_identifier(token.text) || self.is_register(token.text) {
+            return Err(error(
+                token,
+                format!("`{}` is not a valid name", token.text),
+            ));
+        }
+        Ok(token)
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        register_index(text).is_some() || self.aliases.contains_key(text)
+    }
+
+    fn register(&mut self) -> Result<u8, AssemblyError> {
+        let token = self.next()?;
+        self.register_of(token)
+    }
+
+    fn register_of(&self, token: Token<'a>) -> Result<u8, AssemblyError> {
+        register_index(token.text)
+            .or_else(|| self.aliases.get(token.text).copied())
+            .ok_or_else(|| {
+                error(
+                    token,
+                    format!("expected a register but found `{}`", token.text),
+                )
+            })
+    }
+
+    fn is_value(&self, text: &str) -> bool {
+        parse_number(text).is_some() || self.constants.contains_key(text)
+    }
+
+    fn value(&mut self) -> Result<i64, AssemblyError> {
+        let token = self.next()?;
+        self.value_of(token)
+    }
+
+    fn value_of(&self, token: Token<'a>) -> Result<i64, AssemblyError> {
+        parse_number(token.text)
+            .or_else(|| self.constants.get(token.text).copied())
+            .ok_or_else(|| {
+                error(
+                    token,
+                    format!("expected a number but found `{}`", token.text),
+                )
+            })
+    }
+
+    /// A value from -128 to 255.
+    fn byte(&self, token: Token<'a>) -> Result<u8, AssemblyError> {
+        let value = self.value_of(token)?;
+        if !(-128..=255).contains(&value) {
+            return Err(error(token, format!("{value} does not fit in a byte")));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssemblyError> {
+        let token = self.next()?;
+        let value = self.value_of(token)?;
+        if !(0..=15).contains(&value) {
+            return Err(error(token, format!("{value} does not fit in 4 bits")));
+        }
+        Ok(value as u8)
+    }
+
+    fn address(&mut self) -> Result<Address<'a>, AssemblyError> {
+        let token = self.next()?;
+        if self.is_value(token.text) {
+            let value = self.value_of(token)?;
+            return u16::try_from(value)
+                .map(Address::Value)
+                .map_err(|_| error(token, format!("{value} is not an address")));
+        }
+        if !is_identifier(token.text) {
+            return Err(error(
+                token,
+                format!("expected an address but found `{}`", token.text),
+            ));
+        }
+        Ok(Address::Label(token))
+    }
+}
+
+fn error(token: Token<'_>, message: impl Into<String>) -> AssemblyError {
+    AssemblyError {
+        line: token.line,
+        column: token.column,
+        message: message.into(),
+    }
+}
+
+/// Returns the index of `v0` to `vF`, ignoring case.
+fn register_index(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Parses decimal, `0x` hexadecimal and `0b` binary numbers with an optional `-`.
+fn parse_number(text: &str) -> Option<i64> {
+    let (is_negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|character: char| character.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if is_negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|character: char| character.is_alphabetic() || character == '_')
+        && text
+            .chars()
+            .all(|character| character.is_alphanumeric() || matches!(character, '_' | '-' | '.'))
+}
+
+#[test]
+fn test_assemble() {
+    let source = "\
+:const speed 2
+:alias x v3
+: main
+    i := ball
+    x += speed
+    if x == 0x3F then x := 0
+    sprite x v4 2
+    draw
+    jump main
+: draw  # subroutine
+    v0 := random 0xFF
+    return
+: ball
+    \"..####..\"
+    0b10000001
+";
+    let assembly = Assembly::new(source).unwrap();
+    #[rustfmt::skip]
+    let expected = [
+        0xA2, 0x12, 0x73, 0x02, 0x43, 0x3F, 0x63, 0x00, 0xD3, 0x42, 0x22, 0x0E, 0x12, 0x00,
+        0xC0, 0xFF, 0x00, 0xEE, 0x3C, 0x81,
+    ];
+    assert_eq!(assembly.bytes(), expected);
+    assert_eq!(assembly.label("draw"), Some(0x20E));
+    assert_eq!(assembly.line(0x206), Some(6));
+
+    let listing = assembly.to_listing(source);
+    assert!(listing.contains("204  433F6300      if x == 0x3F then x := 0\n"));
+    assert!(listing.contains("          : main\n"));
+}
+#[test]
+fn test_assembly_errors() {
+    let error = |source| Assembly::new(source).unwrap_err();
+    assert_eq!(
+        error("v0 := 5\n  jump nowhere").to_string(),
+        "2:8: the label `nowhere` is not defined"
+    );
+    assert_eq!(
+        error("v0 := 256").to_string(),
+        "1:7: 256 does not fit in a byte"
+    );
+    assert_eq!(error("  vG += 1").to_string(), "1:6: unexpected `+=`");
+    assert_eq!(
+        error(": a\n: a").to_string(),
+        "2:3: the label `a` is already defined on line 1"
+    );
+    assert_eq!(
+        error("if v0 == 1").to_string(),
+        "1:11: unexpected end of the source"
+    );
+    assert_eq!(error("\"..#\"").line, 1);
+
+    let fills_memory = "0 ".repeat(0xFE00);
+    let assembly = Assembly::new(&fills_memory).unwrap();
+    assert_eq!(assembly.line(0xFFFF), Some(1));
+    assert_eq!(assembly.line(0x1FF), None);
+    let too_long = "0 ".repeat(0xFE01);
+    assert_eq!(
+        Assembly::new(&too_long).unwrap_err().to_string(),
+        "1:130049: the program does not fit in memory"
+    );
+    let label_past_end = format!("{}: end", "0 ".repeat(0xFE00));
+    assert_eq!(
+        Assembly::new(&label_past_end).unwrap_err().to_string(),
+        "1:130051: the label is past the end of memory"
+    );
+}
+#[test]
+fn test_disassembly_round_trip() {
+    use crate::disasm::{Disassembly, Syntax};
+    for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/roms")).unwrap() {
+        let path = entry.unwrap().path();
+        let program = std::fs::read(&path).unwrap();
+        let source = Disassembly::new(&program, Syntax::Octo).to_string();
+        let assembly = Assembly::new(&source).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        assert_eq!(assembly.bytes(), program, "{}", path.display());
+    }
+}
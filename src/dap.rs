@@ -0,0 +1,939 @@
+use crate::interpreter::{
+    Condition, ConfigurationBuilder, Debugger, Interpreter, Platform, StopReason, SymbolTable,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, TryRecvError};
+
+mod json;
+
+use json::Json;
+
+/// The only thread of a CHIP-8 program.
+const THREAD_ID: i64 = 1;
+
+/// The `variablesReference` of each scope of a stack frame.
+const REGISTERS_REFERENCE: i64 = 1;
+const TIMERS_REFERENCE: i64 = 2;
+const CALL_STACK_REFERENCE: i64 = 3;
+
+/// The largest `Content-Length` accepted. Requests that write all of a 64 KiB memory stay well below it.
+const MAX_CONTENT_LENGTH: usize = 1 << 20;
+
+/// Where a breakpoint was set, so each kind of `set*Breakpoints` request replaces only its own breakpoints.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum BreakpointOrigin {
+    /// The path of the source file.
+    Source(String),
+    /// A label name or an address, from `setFunctionBreakpoints`.
+    Function,
+    /// An address, from `setInstructionBreakpoints`.
+    Instruction,
+}
+
+/// A Debug Adapter Protocol server that debugs a CHIP-8 program for editors like VS Code.
+///
+/// The `launch` request takes the `program` path and optionally `symbols` (the path of a [SymbolTable],
+/// by default the program path with the `.sym` extension), `platform` (a [Platform::name]) and `stopOnEntry`.
+/// Source breakpoints are mapped to addresses with the symbol table.
+/// Function breakpoints take a label or an address like `0x204`.
+///
+/// Steps execute one instruction. `next` steps over subroutine calls.
+#[derive(Debug, Default)]
+pub struct DapServer {
+    debugger: Option<Debugger>,
+
+    /// The directory the source files of the symbol table are relative to.
+    source_directory: PathBuf,
+
+    /// The address and condition of every requested breakpoint that could be mapped to an address.
+    breakpoints: BTreeMap<BreakpointOrigin, Vec<(u16, Option<Condition>)>>,
+
+    stop_on_entry: bool,
+    /// Set by `continue` until execution stops.
+    running: bool,
+    /// Set by `disconnect`.
+    disconnected: bool,
+
+    /// The `seq` of the last message sent.
+    sequence: i64,
+    /// Messages waiting to be written to the client.
+    outgoing: Vec<Json>,
+    /// Events to send after the response to the current request.
+    events: Vec<Json>,
+}
+
+// initialization
+impl DapServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// accessors
+impl DapServer {
+    /// The debugger of the launched program.
+    pub const fn debugger(&self) -> Option<&Debugger> {
+        self.debugger.as_ref()
+    }
+
+    fn launched(&self) -> Result<&Debugger, String> {
+        self.debugger
+            .as_ref()
+            .ok_or_else(|| "no program has been launched".to_owned())
+    }
+
+    /// Returns the source file and line of `address` as a DAP `Source` and line number.
+    fn source_location(&self, address: u16) -> Option<(Json, usize)> {
+        let symbol_table = self.debugger.as_ref()?.interpreter().symbol_table()?;
+        let (file, line) = symbol_table.location(address)?;
+        let path = self.source_directory.join(file);
+        let source = Json::object([
+            ("name", file.into()),
+            ("path", path.to_string_lossy().as_ref().into()),
+        ]);
+        Some((source, line))
+    }
+
+    /// Returns the first address of `line` in the source file at `path`.
+    fn address_of_line(&self, path: &str, line: usize) -> Option<u16> {
+        let symbol_table = self.debugger.as_ref()?.interpreter().symbol_table()?;
+        let path = Path::new(path);
+        // the symbol table names files the way they were given to the assembler
+        let relative_path = path.strip_prefix(&self.source_directory).ok();
+        [Some(path), relative_path, path.file_name().map(Path::new)]
+            .into_iter()
+            .flatten()
+            .find_map(|file| symbol_table.address_of_line(&file.to_string_lossy(), line))
+    }
+
+    /// Returns the address of a label, or of a number like `0x204` or `516`.
+    fn address_of_name(&self, name: &str) -> Option<u16> {
+        let symbol_table = self
+            .debugger
+            .as_ref()
+            .and_then(|debugger| debugger.interpreter().symbol_table());
+        symbol_table
+            .and_then(|symbol_table| symbol_table.address_of(name))
+            .or_else(|| parse_address(name))
+    }
+
+    fn stack_trace(&self) -> Result<Json, String> {
+        let chip8 = self.launched()?.interpreter();
+        let mut addresses = vec![chip8.program_counter()];
+        let calls = chip8.call_stack().iter().rev();
+        addresses.extend(calls.map(|return_address| return_address.wrapping_sub(2)));
+
+        let frames = addresses.iter().enumerate().map(|(index, &address)| {
+            let name = match chip8
+                .symbol_table()
+                .and_then(|table| table.nearest_label(address))
+            {
+                Some((label, 0)) => label.to_owned(),
+                Some((label, offset)) => format!("{label}+{offset:#X}"),
+                None => format!("{address:#05X}"),
+            };
+            let mut frame = Json::object([
+                ("id", index.into()),
+                ("name", name.into()),
+                ("line", 0i64.into()),
+                ("column", 0i64.into()),
+                (
+                    "instructionPointerReference",
+                    format!("{address:#05X}").into(),
+                ),
+            ]);
+            if let Some((source, line)) = self.source_location(address) {
+                frame.insert("source", source);
+                frame.insert("line", line.into());
+                frame.insert("column", 1i64.into());
+            }
+            frame
+        });
+        Ok(Json::object([
+            ("stackFrames", Json::Array(frames.collect())),
+            ("totalFrames", addresses.len().into()),
+        ]))
+    }
+
+    fn variables(&self, reference: i64) -> Result<Json, String> {
+        let chip8 = self.launched()?.interpreter();
+        let variable = |name: String, value: String| {
+            Json::object([
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", 0i64.into()),
+            ])
+        };
+        let mut variables = Vec::new();
+        match reference {
+            REGISTERS_REFERENCE => {
+                for (index, value) in chip8.registers().iter().enumerate() {
+                    variables.push(variable(format!("V{index:X}"), format!("{value:#04X}")));
+                }
+                for (name, address) in [
+                    ("I", chip8.address_register()),
+                    ("PC", chip8.program_counter()),
+                ] {
+                    let mut register = variable(name.to_owned(), format!("{address:#05X}"));
+                    register.insert("memoryReference", format!("{address:#05X}").into());
+                    variables.push(register);
+                }
+            }
+            TIMERS_REFERENCE => {
+                variables.push(variable("DT".to_owned(), chip8.delay_timer().to_string()));
+                variables.push(variable("ST".to_owned(), chip8.sound_timer().to_string()));
+            }
+            CALL_STACK_REFERENCE => {
+                let return_addresses = chip8.call_stack().iter().rev();
+                for (index, &return_address) in return_addresses.enumerate() {
+                    let value = chip8.describe_address(return_address);
+                    variables.push(variable(format!("#{index}"), value));
+                }
+            }
+            _ => return Err(format!("unknown variables reference {reference}")),
+        }
+        Ok(Json::object([("variables", Json::Array(variables))]))
+    }
+
+    /// `readMemory`: returns the bytes as base64. Bytes past the end of memory are unreadable.
+    fn read_memory(&self, arguments: &Json) -> Result<Json, String> {
+        let memory = self.launched()?.interpreter().memory();
+        let address = memory_address(arguments)?;
+        let count = arguments.get("count").and_then(Json::as_i64).unwrap_or(0);
+        let count = usize::try_from(count).map_err(|_| "`count` must not be negative")?;
+        let start = address.min(memory.len());
+        let end = address.saturating_add(count).min(memory.len());
+        Ok(Json::object([
+            ("address", format!("{address:#05X}").into()),
+            ("data", encode_base64(&memory[start..end]).into()),
+            ("unreadableBytes", (count - (end - start)).into()),
+        ]))
+    }
+}
+
+// mutators
+impl DapServer {
+    pub fn debugger_mut(&mut self) -> Option<&mut Debugger> {
+        self.debugger.as_mut()
+    }
+
+    /// Serves DAP messages from `input` until the client disconnects or `input` ends.
+    ///
+    /// Messages are read on another thread, so a `pause` request interrupts a running program.
+    /// Other requests sent while the program runs are answered once it stops.
+    pub fn run(
+        &mut self,
+        input: impl BufRead + Send + 'static,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut input = input;
+            while let Ok(Some(message)) = read_message(&mut input) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut deferred = VecDeque::new();
+        while !self.disconnected {
+            if self.running {
+                loop {
+                    match receiver.try_recv() {
+                        Ok(message) if command_of(&message) == "pause" => {
+                            self.handle_message(&message)
+                        }
+                        Ok(message) => deferred.push_back(message),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) if deferred.is_empty() => return Ok(()),
+                        Err(TryRecvError::Disconnected) => break,
+                    }
+                }
+                if self.running {
+                    self.run_frame();
+                }
+            } else {
+                let message = match deferred.pop_front() {
+                    Some(message) => message,
+                    None => match receiver.recv() {
+                        Ok(message) => message,
+                        Err(_) => return Ok(()),
+                    },
+                };
+                self.handle_message(&message);
+            }
+
+            for message in self.outgoing.drain(..) {
+                write_message(&mut output, &message)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: &Json) {
+        if message.get("type").and_then(Json::as_str) != Some("request") {
+            return;
+        }
+        let command = command_of(message);
+        let arguments = message.get("arguments").unwrap_or(&Json::Null);
+        let result = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_source_breakpoints(arguments),
+            "setFunctionBreakpoints" => {
+                self.set_address_breakpoints(arguments, BreakpointOrigin::Function, "name")
+            }
+            "setInstructionBreakpoints" => self.set_address_breakpoints(
+                arguments,
+                BreakpointOrigin::Instruction,
+                "instructionReference",
+            ),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.send_stopped("entry", None);
+                } else {
+                    self.running = true;
+                }
+                Ok(Json::Null)
+            }
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([
+                    ("id", THREAD_ID.into()),
+                    ("name", "chip8".into()),
+                ])]),
+            )])),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(scopes()),
+            "variables" => {
+                let reference = arguments.get("variablesReference").and_then(Json::as_i64);
+                self.variables(reference.unwrap_or_default())
+            }
+            "continue" => {
+                self.running = self.debugger.is_some();
+                self.launched()
+                    .map(|_| Json::object([("allThreadsContinued", true.into())]))
+            }
+            "next" => self.step(Debugger::step_over),
+            "stepIn" => self.step(Debugger::step_into),
+            "stepOut" => self.step(Debugger::step_out),
+            "pause" => {
+                self.running = false;
+                self.send_stopped("pause", None);
+                Ok(Json::Null)
+            }
+            "readMemory" => self.read_memory(arguments),
+            "writeMemory" => self.write_memory(arguments),
+            "disconnect" | "terminate" => {
+                self.running = false;
+                self.disconnected = true;
+                Ok(Json::Null)
+            }
+            _ => Err(format!("`{command}` is not supported")),
+        };
+        self.respond(message, result);
+    }
+
+    /// Loads the program and its symbol table. Execution starts with `configurationDone`.
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String> {
+        let program_path = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("launch needs the `program` path")?;
+        let platform = match arguments.get("platform").and_then(Json::as_str) {
+            Some(name) => Platform::ALL
+                .into_iter()
+                .find(|platform| platform.name() == name)
+                .ok_or_else(|| format!("unknown platform `{name}`"))?,
+            None => Platform::CosmacVip,
+        };
+        let symbols_path = match arguments.get("symbols").and_then(Json::as_str) {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(program_path).with_extension("sym"),
+        };
+
+        let mut interpreter: Interpreter = ConfigurationBuilder::preset(platform).build();
+        interpreter
+            .load_program_from_path(program_path)
+            .map_err(|error| format!("could not load {program_path}: {error}"))?;
+        if symbols_path.exists() {
+            let symbol_table = SymbolTable::from_path(&symbols_path)
+                .map_err(|error| format!("could not load {}: {error}", symbols_path.display()))?;
+            interpreter.set_symbol_table(Some(symbol_table));
+        }
+        self.source_directory = symbols_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        self.stop_on_entry = arguments
+            .get("stopOnEntry")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        self.debugger = Some(Debugger::new(interpreter));
+
+        // the client sends the breakpoints after this
+        self.events.push(Json::object([
+            ("type", "event".into()),
+            ("event", "initialized".into()),
+        ]));
+        Ok(Json::Null)
+    }
+
+    /// `setBreakpoints`: replaces the breakpoints of one source file.
+    fn set_source_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(Json::as_str)
+            .ok_or("setBreakpoints needs the `source` path")?;
+        let requested = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+
+        let mut breakpoints = Vec::new();
+        let mut replies = Vec::new();
+        for breakpoint in requested {
+            let line = breakpoint.get("line").and_then(Json::as_i64).unwrap_or(0);
+            let address = usize::try_from(line)
+                .ok()
+                .and_then(|line| self.address_of_line(path, line));
+            let (breakpoint_to_set, mut reply) =
+                breakpoint_reply(breakpoint, address, "no code at this line");
+            reply.insert("line", line.into());
+            breakpoints.extend(breakpoint_to_set);
+            replies.push(reply);
+        }
+        self.breakpoints
+            .insert(BreakpointOrigin::Source(path.to_owned()), breakpoints);
+        self.sync_breakpoints();
+        Ok(Json::object([("breakpoints", Json::Array(replies))]))
+    }
+
+    /// `setFunctionBreakpoints` and `setInstructionBreakpoints`: replaces the breakpoints at addresses
+    /// named by the `key` member of each breakpoint.
+    fn set_address_breakpoints(
+        &mut self,
+        arguments: &Json,
+        origin: BreakpointOrigin,
+        key: &str,
+    ) -> Result<Json, String> {
+        let requested = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+
+        let mut breakpoints = Vec::new();
+        let mut replies = Vec::new();
+        for breakpoint in requested {
+            let name = breakpoint
+                .get(key)
+                .and_then(Json::as_str)
+                .unwrap_or_default();
+            let offset = breakpoint.get("offset").and_then(Json::as_i64).unwrap_or(0);
+            let address = self
+                .address_of_name(name)
+                .and_then(|address| i64::from(address).checked_add(offset))
+                .and_then(|address| u16::try_from(address).ok());
+            let (breakpoint_to_set, reply) =
+                breakpoint_reply(breakpoint, address, "unknown label or address");
+            breakpoints.extend(breakpoint_to_set);
+            replies.push(reply);
+        }
+        self.breakpoints.insert(origin, breakpoints);
+        self.sync_breakpoints();
+        Ok(Json::object([("breakpoints", Json::Array(replies))]))
+    }
+
+    /// Replaces the breakpoints of the debugger with every breakpoint that has an address.
+    fn sync_breakpoints(&mut self) {
+        let Some(debugger) = &mut self.debugger else {
+            return;
+        };
+        debugger.clear_breakpoints();
+        for &(address, condition) in self.breakpoints.values().flatten() {
+            match condition {
+                Some(condition) => debugger.add_conditional_breakpoint(address, condition),
+                None => debugger.add_breakpoint(address),
+            }
+        }
+    }
+
+    fn step(&mut self, step: fn(&mut Debugger) -> StopReason) -> Result<Json, String> {
+        let debugger = self
+            .debugger
+            .as_mut()
+            .ok_or("no program has been launched")?;
+        let stop_reason = step(debugger);
+        self.stopped(stop_reason, "step");
+        Ok(Json::Null)
+    }
+
+    /// Runs one frame of a running program and reports it if execution stopped.
+    fn run_frame(&mut self) {
+        let Some(debugger) = &mut self.debugger else {
+            self.running = false;
+            return;
+        };
+        match debugger.run_frame() {
+            StopReason::FrameComplete => {}
+            stop_reason => {
+                self.running = false;
+                self.stopped(stop_reason, "step");
+                self.send_events();
+            }
+        }
+    }
+
+    /// Sends the events for `stop_reason`. `step_reason` is the DAP reason of a finished step.
+    fn stopped(&mut self, stop_reason: StopReason, step_reason: &str) {
+        let Some(debugger) = &self.debugger else {
+            return;
+        };
+        let description = debugger.describe_stop(&stop_reason);
+        match stop_reason {
+            StopReason::Breakpoint { .. } => self.send_stopped("breakpoint", Some(description)),
+            StopReason::Watchpoint { .. } => {
+                self.send_stopped("data breakpoint", Some(description))
+            }
+            StopReason::Step | StopReason::FrameComplete | StopReason::WaitingForKey => {
+                self.send_stopped(step_reason, Some(description))
+            }
+            StopReason::Error(_) => {
+                self.send_event(
+                    "output",
+                    Json::object([
+                        ("category", "stderr".into()),
+                        ("output", format!("{description}\n").into()),
+                    ]),
+                );
+                self.send_stopped("exception", Some(description));
+            }
+            StopReason::Exited => {
+                self.send_event("exited", Json::object([("exitCode", 0i64.into())]));
+                self.send_event("terminated", Json::object([]));
+            }
+        }
+    }
+
+    fn send_stopped(&mut self, reason: &str, description: Option<String>) {
+        let mut body = Json::object([
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        if let Some(description) = description {
+            body.insert("description", description.into());
+        }
+        self.send_event("stopped", body);
+    }
+
+    /// Queues an event to be sent after the response to the current request.
+    fn send_event(&mut self, event: &str, body: Json) {
+        self.events.push(Json::object([
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ]));
+    }
+
+    /// Queues the response to `request` followed by the events of the request.
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) {
+        let request_sequence = request.get("seq").and_then(Json::as_i64).unwrap_or(0);
+        let mut response = Json::object([
+            ("type", "response".into()),
+            ("request_seq", request_sequence.into()),
+            ("success", result.is_ok().into()),
+            ("command", command_of(request).into()),
+        ]);
+        match result {
+            Ok(Json::Null) => {}
+            Ok(body) => response.insert("body", body),
+            Err(message) => response.insert("message", message.into()),
+        }
+        self.send(response);
+        self.send_events();
+    }
+
+    fn send_events(&mut self) {
+        for event in std::mem::take(&mut self.events) {
+            self.send(event);
+        }
+    }
+
+    /// Numbers `message` and queues it to be written to the client.
+    fn send(&mut self, mut message: Json) {
+        self.sequence += 1;
+        message.insert("seq", self.sequence.into());
+        self.outgoing.push(message);
+    }
+
+    /// `writeMemory`: writes base64 `data` at `memoryReference` plus `offset`.
+    fn write_memory(&mut self, arguments: &Json) -> Result<Json, String> {
+        let address = memory_address(arguments)?;
+        let data = arguments
+            .get("data")
+            .and_then(Json::as_str)
+            .and_then(decode_base64)
+            .ok_or("`data` must be base64")?;
+        let debugger = self
+            .debugger
+            .as_mut()
+            .ok_or("no program has been launched")?;
+        let chip8 = debugger.interpreter_mut();
+        chip8
+            .write_memory(address, &data)
+            .map_err(|error| error.to_string())?;
+        Ok(Json::object([("bytesWritten", data.len().into())]))
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object([
+        ("supportsConfigurationDoneRequest", true.into()),
+        ("supportsConditionalBreakpoints", true.into()),
+        ("supportsFunctionBreakpoints", true.into()),
+        ("supportsInstructionBreakpoints", true.into()),
+        ("supportsReadMemoryRequest", true.into()),
+        ("supportsWriteMemoryRequest", true.into()),
+        ("supportsTerminateRequest", true.into()),
+    ])
+}
+
+fn scopes() -> Json {
+    let scope = |name: &str, reference: i64| {
+        Json::object([
+            ("name", name.into()),
+            ("variablesReference", reference.into()),
+            ("expensive", false.into()),
+        ])
+    };
+    Json::object([(
+        "scopes",
+        Json::Array(vec![
+            scope("Registers", REGISTERS_REFERENCE),
+            scope("Timers", TIMERS_REFERENCE),
+            scope("Call Stack", CALL_STACK_REFERENCE),
+        ]),
+    )])
+}
+
+/// Returns the breakpoint to set for a requested breakpoint at `address`, and the `Breakpoint` to reply with.
+/// Nothing is set if there is no address or the `condition` is invalid.
+fn breakpoint_reply(
+    breakpoint: &Json,
+    address: Option<u16>,
+    unmapped_message: &str,
+) -> (Option<(u16, Option<Condition>)>, Json) {
+    let condition = breakpoint
+        .get("condition")
+        .and_then(Json::as_str)
+        .filter(|condition| !condition.trim().is_empty())
+        .map(str::parse::<Condition>)
+        .transpose();
+    let mut reply = Json::object([("verified", false.into())]);
+    if let Some(address) = address {
+        reply.insert("instructionReference", format!("{address:#05X}").into());
+    }
+    match (address, condition) {
+        (_, Err(error)) => reply.insert("message", format!("invalid condition: {error}").into()),
+        (None, Ok(_)) => reply.insert("message", unmapped_message.into()),
+        (Some(address), Ok(condition)) => {
+            reply.insert("verified", true.into());
+            return (Some((address, condition)), reply);
+        }
+    }
+    (None, reply)
+}
+
+/// Reads an address like `0x204` or `516`.
+fn parse_address(text: &str) -> Option<u16> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hexadecimal) => u16::from_str_radix(hexadecimal, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Returns `memoryReference` plus `offset` of a memory request.
+fn memory_address(arguments: &Json) -> Result<usize, String> {
+    let reference = arguments
+        .get("memoryReference")
+        .and_then(Json::as_str)
+        .and_then(parse_address)
+        .ok_or("`memoryReference` must be an address like 0x200")?;
+    let offset = arguments.get("offset").and_then(Json::as_i64).unwrap_or(0);
+    i64::from(reference)
+        .checked_add(offset)
+        .and_then(|address| usize::try_from(address).ok())
+        .ok_or_else(|| "the address is outside of memory".to_owned())
+}
+
+fn command_of(message: &Json) -> &str {
+    message
+        .get("command")
+        .and_then(Json::as_str)
+        .unwrap_or_default()
+}
+
+/// Reads a message framed by a `Content-Length` header. Returns `None` at the end of `input`.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().ok();
+        }
+    }
+    let content_length = content_length.unwrap_or_default();
+    if content_length > MAX_CONTENT_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the content length {content_length} is larger than {MAX_CONTENT_LENGTH}"),
+        ));
+    }
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    let text = String::from_utf8_lossy(&content);
+    Json::parse(&text)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | (byte as u32) << (16 - 8 * index)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3F;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut bits = 0;
+    for character in text.trim_end_matches('=').bytes() {
+        let sextet = BASE64_ALPHABET.iter().position(|&byte| byte == character)?;
+        group = (group << 6) | sextet as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[test]
+fn test_base64() {
+    for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", b"\xFF\x00\x10"] {
+        assert_eq!(decode_base64(&encode_base64(bytes)).unwrap(), bytes);
+    }
+    assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+}
+#[test]
+fn test_untrusted_input() {
+    let arguments = Json::object([
+        ("memoryReference", "0x20E".into()),
+        ("offset", i64::MAX.into()),
+    ]);
+    assert!(memory_address(&arguments).is_err());
+    let mut input = io::Cursor::new("Content-Length: 18446744073709551615\r\n\r\n");
+    let error = read_message(&mut input).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+#[test]
+fn test_deeply_nested_input() {
+    let content = "[".repeat(200_000);
+    let message = format!("Content-Length: {}\r\n\r\n{content}", content.len());
+    let error = read_message(&mut io::Cursor::new(message)).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "arrays and objects are nested too deeply at byte 128"
+    );
+}
+#[test]
+fn test_dap_session() {
+    let source = "\
+: main
+    v3 := 0
+: loop
+    v3 += 1
+    count
+    jump loop
+: count
+    i := counter
+    save v3
+    return
+: counter
+    0
+";
+    let directory = std::env::temp_dir().join(format!("chip8_dap_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let assembly = crate::asm::Assembly::new(source).unwrap();
+    let program_path = directory.join("game.ch8");
+    std::fs::write(&program_path, assembly.bytes()).unwrap();
+    let symbols = assembly.symbol_table("game.8o").to_text();
+    std::fs::write(directory.join("game.sym"), symbols).unwrap();
+    let source_path = directory.join("game.8o");
+
+    let requests = [
+        ("initialize", Json::object([("adapterID", "chip8".into())])),
+        (
+            "launch",
+            Json::object([("program", program_path.to_string_lossy().as_ref().into())]),
+        ),
+        (
+            "setBreakpoints",
+            Json::object([
+                (
+                    "source",
+                    Json::object([("path", source_path.to_string_lossy().as_ref().into())]),
+                ),
+                (
+                    "breakpoints",
+                    Json::Array(vec![
+                        Json::object([("line", 8i64.into()), ("condition", "V3 == 2".into())]),
+                        Json::object([("line", 1i64.into())]),
+                    ]),
+                ),
+            ]),
+        ),
+        ("configurationDone", Json::Null),
+        ("stackTrace", Json::object([("threadId", THREAD_ID.into())])),
+        (
+            "variables",
+            Json::object([("variablesReference", REGISTERS_REFERENCE.into())]),
+        ),
+        ("next", Json::object([("threadId", THREAD_ID.into())])),
+        (
+            "readMemory",
+            Json::object([("memoryReference", "0x20E".into()), ("count", 4i64.into())]),
+        ),
+        ("stepOut", Json::object([("threadId", THREAD_ID.into())])),
+        ("disconnect", Json::Null),
+    ];
+    let mut input = Vec::new();
+    for (index, (command, arguments)) in requests.into_iter().enumerate() {
+        let mut request = Json::object([
+            ("seq", (index + 1).into()),
+            ("type", "request".into()),
+            ("command", command.into()),
+        ]);
+        if arguments != Json::Null {
+            request.insert("arguments", arguments);
+        }
+        write_message(&mut input, &request).unwrap();
+    }
+    let mut output = Vec::new();
+    DapServer::new()
+        .run(io::Cursor::new(input), &mut output)
+        .unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    let mut output = io::Cursor::new(output);
+    let mut messages = Vec::new();
+    while let Some(message) = read_message(&mut output).unwrap() {
+        messages.push(message);
+    }
+    let kinds: Vec<_> = messages
+        .iter()
+        .map(|message| {
+            let kind = message.get("command").or_else(|| message.get("event"));
+            kind.and_then(Json::as_str).unwrap()
+        })
+        .collect();
+    let expected_kinds = [
+        "initialize",
+        "launch",
+        "initialized",
+        "setBreakpoints",
+        "configurationDone",
+        "stopped",
+        "stackTrace",
+        "variables",
+        "next",
+        "stopped",
+        "readMemory",
+        "stepOut",
+        "stopped",
+        "disconnect",
+    ];
+    assert_eq!(kinds, expected_kinds);
+    for (index, message) in messages.iter().enumerate() {
+        assert_eq!(message.get("seq"), Some(&Json::from(index + 1)));
+        assert_ne!(message.get("success"), Some(&Json::Bool(false)));
+    }
+
+    let set_breakpoints = messages[3].to_string();
+    assert!(
+        set_breakpoints.contains(r#"{"verified":true,"instructionReference":"0x208","line":8}"#)
+    );
+    assert!(
+        set_breakpoints.contains(r#"{"verified":false,"message":"no code at this line","line":1}"#)
+    );
+    let stopped = messages[5].get("body").unwrap();
+    assert_eq!(stopped.get("reason"), Some(&"breakpoint".into()));
+    assert_eq!(
+        stopped.get("description"),
+        Some(&"breakpoint at count (game.8o:8)".into())
+    );
+
+    let frames = messages[6].get("body").unwrap().get("stackFrames").unwrap();
+    let frames = frames.as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].get("name"), Some(&"count".into()));
+    assert_eq!(frames[0].get("line"), Some(&8i64.into()));
+    assert_eq!(frames[1].get("name"), Some(&"loop+0x2".into()));
+
+    let variables = messages[7].to_string();
+    assert!(variables.contains(r#"{"name":"V3","value":"0x02","variablesReference":0}"#));
+    assert!(variables.contains(r#""name":"PC","value":"0x208""#));
+
+    let memory = messages[10].get("body").unwrap();
+    assert_eq!(
+        memory.get("data"),
+        Some(&encode_base64(&[0, 0, 0, 1]).into())
+    );
+    let stepped_out = messages[12].get("body").unwrap();
+    assert_eq!(
+        stepped_out.get("description"),
+        Some(&"stepped to loop+0x4 (game.8o:6)".into())
+    );
+}
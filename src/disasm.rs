@@ -0,0 +1,628 @@
+use crate::instruction::Instruction;
+use crate::interpreter::builder::{DEFAULT_PROGRAM_START, XO_CHIP_MEMORY_SIZE};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::ops::Range;
+
+mod control_flow;
+
+pub use control_flow::{BasicBlock, ControlFlowGraph, Edge, EdgeKind, SelfModifyingWrite};
+
+/// How many instructions after an `Annn` are searched for the `Dxyn` that draws its sprite.
+const SPRITE_SEARCH_DISTANCE: usize = 8;
+/// The column at which the address comments of a listing start.
+const COMMENT_COLUMN: usize = 32;
+
+/// The assembly dialect a [Disassembly] is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Syntax {
+    /// The syntax of the Octo assembler, e.g. `v0 := 0x05`.
+    #[default]
+    Octo,
+    /// The mnemonics of Cowgod's Chip-8 technical reference, e.g. `LD V0, 0x05`.
+    Cowgod,
+}
+
+// accessors
+impl Syntax {
+    const fn comment(self) -> &'static str {
+        match self {
+            Self::Octo => "#",
+            Self::Cowgod => ";",
+        }
+    }
+
+    fn label_declaration(self, name: &str) -> String {
+        match self {
+            Self::Octo => format!(": {name}"),
+            Self::Cowgod => format!("{name}:"),
+        }
+    }
+
+    fn byte(self, byte: u8) -> String {
+        match self {
+            Self::Octo => format!("{byte:#04X}"),
+            Self::Cowgod => format!("DB {byte:#04X}"),
+        }
+    }
+
+    /// Writes `instruction` in this syntax.
+    ///
+    /// `long_address` is the operand of `F000 nnnn`. `address_name` turns jump, call and `I` targets into text.
+    pub fn format_instruction(
+        self,
+        instruction: Instruction,
+        long_address: u16,
+        address_name: impl Fn(u16) -> String,
+    ) -> String {
+        use Instruction::*;
+        // a bare number is a data byte in Octo, so calls to unlabelled addresses need `:call`
+        let octo_call = |name: String| {
+            if name.starts_with("0x") {
+                format!(":call {name}")
+            } else {
+                name
+            }
+        };
+        match self {
+            #[rustfmt::skip]
+            Self::Octo => match instruction {
+                AssemblyRoutine { nnn }                      => format!("native {}", address_name(nnn)),
+                ClearDisplay                                 => "clear".to_owned(),
+                ReturnSubroutine                             => "return".to_owned(),
+                ScrollDown { n }                             => format!("scroll-down {n}"),
+                ScrollUp { n }                               => format!("scroll-up {n}"),
+                ScrollRight                                  => "scroll-right".to_owned(),
+                ScrollLeft                                   => "scroll-left".to_owned(),
+                Exit                                         => "exit".to_owned(),
+                LowResolution                                => "lores".to_owned(),
+                HighResolution                               => "hires".to_owned(),
+                Jump { nnn }                                 => format!("jump {}", address_name(nnn)),
+                CallSubroutine { nnn }                       => octo_call(address_name(nnn)),
+                SkipIfEqualValue { x, kk }                   => format!("if v{x:X} != {kk:#04X} then"),
+                SkipIfNotEqualValue { x, kk }                => format!("if v{x:X} == {kk:#04X} then"),
+                SkipIfEqual { x, y }                         => format!("if v{x:X} != v{y:X} then"),
+                StoreRegisterRange { x, y }                  => format!("save v{x:X} - v{y:X}"),
+                LoadRegisterRange { x, y }                   => format!("load v{x:X} - v{y:X}"),
+                AssignValue { x, kk }                        => format!("v{x:X} := {kk:#04X}"),
+                AddAssignValue { x, kk }                     => format!("v{x:X} += {kk:#04X}"),
+                Assign { x, y }                              => format!("v{x:X} := v{y:X}"),
+                BitwiseOrAssign { x, y }                     => format!("v{x:X} |= v{y:X}"),
+                BitwiseAndAssign { x, y }                    => format!("v{x:X} &= v{y:X}"),
+                BitwiseXorAssign { x, y }                    => format!("v{x:X} ^= v{y:X}"),
+                AddAssign { x, y }                           => format!("v{x:X} += v{y:X}"),
+                SubAssign { x, y }                           => format!("v{x:X} -= v{y:X}"),
+                RightShiftAssign { x, y }                    => format!("v{x:X} >>= v{y:X}"),
+                SubAssignSwapped { x, y }                    => format!("v{x:X} =- v{y:X}"),
+                LeftShiftAssign { x, y }                     => format!("v{x:X} <<= v{y:X}"),
+                SkipIfNotEqual { x, y }                      => format!("if v{x:X} == v{y:X} then"),
+                AddressRegisterAssign { nnn }                => format!("i := {}", address_name(nnn)),
+                JumpOffset { nnn, .. }                       => format!("jump0 {}", address_name(nnn)),
+                RandomNumberAssign { x, kk }                 => format!("v{x:X} := random {kk:#04X}"),
+                DrawSprite { x, y, n }                       => format!("sprite v{x:X} v{y:X} {n}"),
+                SkipOnKeyPressed { x }                       => format!("if v{x:X} -key then"),
+                SkipOnKeyNotPressed { x }                    => format!("if v{x:X} key then"),
+                AddressRegisterAssignLong                    => format!("i := long {}", address_name(long_address)),
+                SelectPlanes { n }                           => format!("plane {n}"),
+                StoreAudioPattern                            => "audio".to_owned(),
+                StoreDelayTimer { x }                        => format!("v{x:X} := delay"),
+                WaitForKeyPress { x }                        => format!("v{x:X} := key"),
+                DelayTimerAssign { x }                       => format!("delay := v{x:X}"),
+                SoundTimerAssign { x }                       => format!("buzzer := v{x:X}"),
+                AddressRegisterAddAssign { x }               => format!("i += v{x:X}"),
+                AddressRegisterAssignCharacterAddress { x }  => format!("i := hex v{x:X}"),
+                AddressRegisterAssignBigCharacterAddress { x } => format!("i := bighex v{x:X}"),
+                StoreBinaryCodedDecimalAddress { x }         => format!("bcd v{x:X}"),
+                PitchAssign { x }                            => format!("pitch := v{x:X}"),
+                StoreVariableRegisters { x }                 => format!("save v{x:X}"),
+                LoadVariableRegisters { x }                  => format!("load v{x:X}"),
+                StoreFlagRegisters { x }                     => format!("saveflags v{x:X}"),
+                LoadFlagRegisters { x }                      => format!("loadflags v{x:X}"),
+            },
+            #[rustfmt::skip]
+            Self::Cowgod => match instruction {
+                AssemblyRoutine { nnn }                      => format!("SYS {}", address_name(nnn)),
+                ClearDisplay                                 => "CLS".to_owned(),
+                ReturnSubroutine                             => "RET".to_owned(),
+                ScrollDown { n }                             => format!("SCD {n}"),
+                ScrollUp { n }                               => format!("SCU {n}"),
+                ScrollRight                                  => "SCR".to_owned(),
+                ScrollLeft                                   => "SCL".to_owned(),
+                Exit                                         => "EXIT".to_owned(),
+                LowResolution                                => "LOW".to_owned(),
+                HighResolution                               => "HIGH".to_owned(),
+                Jump { nnn }                                 => format!("JP {}", address_name(nnn)),
+                CallSubroutine { nnn }                       => format!("CALL {}", address_name(nnn)),
+                SkipIfEqualValue { x, kk }                   => format!("SE V{x:X}, {kk:#04X}"),
+                SkipIfNotEqualValue { x, kk }                => format!("SNE V{x:X}, {kk:#04X}"),
+                SkipIfEqual { x, y }                         => format!("SE V{x:X}, V{y:X}"),
+                StoreRegisterRange { x, y }                  => format!("SAVE V{x:X}-V{y:X}"),
+                LoadRegisterRange { x, y }                   => format!("LOAD V{x:X}-V{y:X}"),
+                AssignValue { x, kk }                        => format!("LD V{x:X}, {kk:#04X}"),
+                AddAssignValue { x, kk }                     => format!("ADD V{x:X}, {kk:#04X}"),
+                Assign { x, y }                              => format!("LD V{x:X}, V{y:X}"),
+                BitwiseOrAssign { x, y }                     => format!("OR V{x:X}, V{y:X}"),
+                BitwiseAndAssign { x, y }                    => format!("AND V{x:X}, V{y:X}"),
+                BitwiseXorAssign { x, y }                    => format!("XOR V{x:X}, V{y:X}"),
+                AddAssign { x, y }                           => format!("ADD V{x:X}, V{y:X}"),
+                SubAssign { x, y }                           => format!("SUB V{x:X}, V{y:X}"),
+                RightShiftAssign { x, y }                    => format!("SHR V{x:X}, V{y:X}"),
+                SubAssignSwapped { x, y }                    => format!("SUBN V{x:X}, V{y:X}"),
+                LeftShiftAssign { x, y }                     => format!("SHL V{x:X}, V{y:X}"),
+                SkipIfNotEqual { x, y }                      => format!("SNE V{x:X}, V{y:X}"),
+                AddressRegisterAssign { nnn }                => format!("LD I, {}", address_name(nnn)),
+                JumpOffset { nnn, .. }                       => format!("JP V0, {}", address_name(nnn)),
+                RandomNumberAssign { x, kk }                 => format!("RND V{x:X}, {kk:#04X}"),
+                DrawSprite { x, y, n }                       => format!("DRW V{x:X}, V{y:X}, {n}"),
+                SkipOnKeyPressed { x }                       => format!("SKP V{x:X}"),
+                SkipOnKeyNotPressed { x }                    => format!("SKNP V{x:X}"),
+                AddressRegisterAssignLong                    => format!("LD I, LONG {}", address_name(long_address)),
+                SelectPlanes { n }                           => format!("PLANE {n}"),
+                StoreAudioPattern                            => "AUDIO".to_owned(),
+                StoreDelayTimer { x }                        => format!("LD V{x:X}, DT"),
+                WaitForKeyPress { x }                        => format!("LD V{x:X}, K"),
+                DelayTimerAssign { x }                       => format!("LD DT, V{x:X}"),
+                SoundTimerAssign { x }                       => format!("LD ST, V{x:X}"),
+                AddressRegisterAddAssign { x }               => format!("ADD I, V{x:X}"),
+                AddressRegisterAssignCharacterAddress { x }  => format!("LD F, V{x:X}"),
+                AddressRegisterAssignBigCharacterAddress { x } => format!("LD HF, V{x:X}"),
+                StoreBinaryCodedDecimalAddress { x }         => format!("LD B, V{x:X}"),
+                PitchAssign { x }                            => format!("PITCH V{x:X}"),
+                StoreVariableRegisters { x }                 => format!("LD [I], V{x:X}"),
+                LoadVariableRegisters { x }                  => format!("LD V{x:X}, [I]"),
+                StoreFlagRegisters { x }                     => format!("LD R, V{x:X}"),
+                LoadFlagRegisters { x }                      => format!("LD V{x:X}, R"),
+            },
+        }
+    }
+}
+
+/// Why an address of a [Disassembly] got a label. Labels of more important kinds are listed later,
+/// a subroutine that is also jumped to is named as a subroutine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LabelKind {
+    /// The target of an `Annn` that is not drawn as a sprite.
+    Data,
+    /// The target of an `Annn` that is drawn by a `Dxyn`.
+    Sprite,
+    /// The target of a `1nnn` or `Bnnn`.
+    Jump,
+    /// The target of a `2nnn`.
+    Subroutine,
+}
+
+// accessors
+impl LabelKind {
+    /// Returns the generated label name of `address`, e.g. `sub_2A4`.
+    pub fn name(self, address: u16) -> String {
+        let prefix = match self {
+            Self::Data => "data",
+            Self::Sprite => "sprite",
+            Self::Jump => "label",
+            Self::Subroutine => "sub",
+        };
+        format!("{prefix}_{address:03X}")
+    }
+}
+
+/// A single line of a [Disassembly].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Line {
+    Instruction {
+        address: u16,
+        instruction: Instruction,
+    },
+    /// Bytes that are not disassembled. Either sprites or bytes that are not a known opcode.
+    Data {
+        address: u16,
+        length: u16,
+        is_sprite: bool,
+    },
+}
+
+// accessors
+impl Line {
+    pub const fn address(&self) -> u16 {
+        match *self {
+            Self::Instruction { address, .. } | Self::Data { address, .. } => address,
+        }
+    }
+
+    /// The number of bytes this line covers.
+    pub const fn size(&self) -> u16 {
+        match *self {
+            Self::Instruction { instruction, .. } => instruction.size(),
+            Self::Data { length, .. } => length,
+        }
+    }
+}
+
+/// An annotated linear disassembly of a program.
+///
+/// Jump, call and `I` targets get generated labels. Bytes that an `Annn` points to and a `Dxyn`
+/// a few instructions later draws are shown as sprite data instead of instructions.
+/// Format it with `to_string()` to get the listing:
+/// ```
+/// use chip8::disasm::{Disassembly, Syntax};
+/// let disassembly = Disassembly::new(&[0x00, 0xE0, 0x12, 0x00], Syntax::Cowgod);
+/// assert!(disassembly.to_string().contains("JP label_200"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    syntax: Syntax,
+    program_start: u16,
+    program_data: Vec<u8>,
+    lines: Vec<Line>,
+    labels: BTreeMap<u16, LabelKind>,
+    sprites: Vec<Range<usize>>,
+}
+
+// initialization
+impl Disassembly {
+    /// Disassembles a program loaded at [DEFAULT_PROGRAM_START].
+    pub fn new(program_data: &[u8], syntax: Syntax) -> Self {
+        Self::with_program_start(program_data, DEFAULT_PROGRAM_START as u16, syntax)
+    }
+
+    /// Bytes that would be loaded past the 16 bit address space are left out.
+    pub fn with_program_start(program_data: &[u8], program_start: u16, syntax: Syntax) -> Self {
+        let length = program_data
+            .len()
+            .min(XO_CHIP_MEMORY_SIZE - program_start as usize);
+        let mut disassembly = Self {
+            syntax,
+            program_start,
+            program_data: program_data[..length].to_vec(),
+            lines: Vec::new(),
+            labels: BTreeMap::new(),
+            sprites: Vec::new(),
+        };
+
+        // sprites found in the first pass can hide instructions that pointed at other sprites
+        for _ in 0..4 {
+            disassembly.lines = disassembly.decode_lines();
+            let sprites = disassembly.find_sprites();
+            if sprites == disassembly.sprites {
+                break;
+            }
+            disassembly.sprites = sprites;
+        }
+        disassembly.lines = disassembly.decode_lines();
+        disassembly.labels = disassembly.find_labels();
+        disassembly
+    }
+}
+
+// accessors
+impl Disassembly {
+    pub const fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    pub const fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// The address ranges that were detected as sprite data.
+    pub fn sprites(&self) -> &[Range<usize>] {
+        &self.sprites
+    }
+
+    /// Returns the name of the label at `address`.
+    pub fn label(&self, address: u16) -> Option<String> {
+        self.labels.get(&address).map(|kind| kind.name(address))
+    }
+
+    /// Returns every labelled address and why it was labelled.
+    pub const fn labels(&self) -> &BTreeMap<u16, LabelKind> {
+        &self.labels
+    }
+
+    /// The address after the last byte of the program. Up to `0x10000`, so it does not always fit in 16 bits.
+    fn program_end(&self) -> usize {
+        self.program_start as usize + self.program_data.len()
+    }
+
+    fn bytes(&self, address: u16, length: u16) -> &[u8] {
+        let start = (address - self.program_start) as usize;
+        &self.program_data[start..start + length as usize]
+    }
+
+    /// Returns the 16 bit address after an `F000` at `address`.
+    fn long_address(&self, address: u16) -> u16 {
+        let start = (address - self.program_start) as usize + 2;
+        let bytes = &self.program_data[start..start + 2];
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn address_name(&self, address: u16) -> String {
+        self.label(address)
+            .unwrap_or_else(|| format!("{address:#05X}"))
+    }
+
+    fn decode_lines(&self) -> Vec<Line> {
+        let program_end = self.program_end();
+        let mut lines = Vec::new();
+        let mut address = self.program_start as usize;
+        while address < program_end {
+            if let Some(sprite) = self.sprites.iter().find(|sprite| sprite.contains(&address)) {
+                let end = sprite.end.min(program_end);
+                lines.push(Line::Data {
+                    address: address as u16,
+                    length: (end - address) as u16,
+                    is_sprite: true,
+                });
+                address = end;
+                continue;
+            }
+
+            let next_sprite_start = self
+                .sprites
+                .iter()
+                .map(|sprite| sprite.start)
+                .filter(|&start| start > address)
+                .min()
+                .unwrap_or(program_end)
+                .min(program_end);
+            let available = next_sprite_start - address;
+            if available < 2 {
+                lines.push(Line::Data {
+                    address: address as u16,
+                    length: available as u16,
+                    is_sprite: false,
+                });
+                address += available;
+                continue;
+            }
+
+            let bytes = self.bytes(address as u16, 2);
+            match Instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]])) {
+                Ok(instruction) if instruction.size() as usize <= available => {
+                    lines.push(Line::Instruction {
+                        address: address as u16,
+                        instruction,
+                    });
+                    address += instruction.size() as usize;
+                }
+                _ => {
+                    lines.push(Line::Data {
+                        address: address as u16,
+                        length: 2,
+                        is_sprite: false,
+                    });
+                    address += 2;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Finds the `Annn` instructions that are followed by a `Dxyn` before `I` changes or the code branches away.
+    fn find_sprites(&self) -> Vec<Range<usize>> {
+        use Instruction::*;
+        let mut sprites: Vec<Range<usize>> = Vec::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let Line::Instruction {
+                address,
+                instruction,
+            } = *line
+            else {
+                continue;
+            };
+            let sprite_start = match instruction {
+                AddressRegisterAssign { nnn } => nnn as usize,
+                AddressRegisterAssignLong => self.long_address(address) as usize,
+                _ => continue,
+            };
+            if !(self.program_start as usize..self.program_end()).contains(&sprite_start) {
+                continue;
+            }
+
+            for line in self.lines[i + 1..].iter().take(SPRITE_SEARCH_DISTANCE) {
+                let Line::Instruction { instruction, .. } = *line else {
+                    break;
+                };
+                match instruction {
+                    DrawSprite { n, .. } => {
+                        let sprite_length = if n == 0 { 32 } else { n as usize };
+                        let sprite_end = (sprite_start + sprite_length).min(self.program_end());
+                        sprites.push(sprite_start..sprite_end);
+                        break;
+                    }
+                    AddressRegisterAssign { .. }
+                    | AddressRegisterAssignLong
+                    | AddressRegisterAddAssign { .. }
+                    | AddressRegisterAssignCharacterAddress { .. }
+                    | AddressRegisterAssignBigCharacterAddress { .. }
+                    | StoreBinaryCodedDecimalAddress { .. }
+                    | StoreVariableRegisters { .. }
+                    | LoadVariableRegisters { .. }
+                    | Jump { .. }
+                    | JumpOffset { .. }
+                    | CallSubroutine { .. }
+                    | ReturnSubroutine
+                    | Exit => break,
+                    _ => {}
+                }
+            }
+        }
+
+        // merge overlapping sprites
+        sprites.sort_by_key(|sprite| sprite.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for sprite in sprites {
+            match merged.last_mut() {
+                Some(last) if sprite.start < last.end => last.end = last.end.max(sprite.end),
+                _ => merged.push(sprite),
+            }
+        }
+        merged
+    }
+
+    /// Labels every jump, call and `I` target that starts a line.
+    fn find_labels(&self) -> BTreeMap<u16, LabelKind> {
+        use Instruction::*;
+        let mut labels = BTreeMap::new();
+        for line in &self.lines {
+            let Line::Instruction {
+                address,
+                instruction,
+            } = *line
+            else {
+                continue;
+            };
+            let (target, kind) = match instruction {
+                Jump { nnn } | JumpOffset { nnn, .. } => (nnn, LabelKind::Jump),
+                CallSubroutine { nnn } => (nnn, LabelKind::Subroutine),
+                AddressRegisterAssign { nnn } => (nnn, LabelKind::Data),
+                AddressRegisterAssignLong => (self.long_address(address), LabelKind::Data),
+                _ => continue,
+            };
+            let kind = match kind {
+                LabelKind::Data
+                    if self
+                        .sprites
+                        .iter()
+                        .any(|sprite| sprite.start == target as usize) =>
+                {
+                    LabelKind::Sprite
+                }
+                kind => kind,
+            };
+            let label = labels.entry(target).or_insert(kind);
+            *label = kind.max(*label);
+        }
+
+        let line_starts: Vec<u16> = self.lines.iter().map(Line::address).collect();
+        labels.retain(|address, _| line_starts.binary_search(address).is_ok());
+        labels
+    }
+}
+
+// mutators
+impl Disassembly {
+    pub fn set_syntax(&mut self, syntax: Syntax) {
+        self.syntax = syntax;
+    }
+}
+
+impl std::fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let comment = self.syntax.comment();
+        let mut write_line = |text: &str, address: u16, bytes: &[u8], note: &str| {
+            let mut hex = String::new();
+            for byte in bytes {
+                write!(hex, "{byte:02X}")?;
+            }
+            let annotation = format!("{comment} {address:03X}  {hex:<8}{note}");
+            writeln!(f, "    {text:COMMENT_COLUMN$}{}", annotation.trim_end())
+        };
+
+        for line in &self.lines {
+            let address = line.address();
+            if let Some(name) = self.label(address) {
+                write_line(&self.syntax.label_declaration(&name), address, &[], "")?;
+            }
+
+            match *line {
+                Line::Instruction { instruction, .. } => {
+                    let long_address = match instruction {
+                        Instruction::AddressRegisterAssignLong => self.long_address(address),
+                        _ => 0,
+                    };
+                    let text = self
+                        .syntax
+                        .format_instruction(instruction, long_address, |a| self.address_name(a));
+                    write_line(&text, address, self.bytes(address, instruction.size()), "")?;
+                }
+                // one line per row so the sprite can be seen in the comments
+                Line::Data {
+                    length,
+                    is_sprite: true,
+                    ..
+                } => {
+                    for (offset, &byte) in self.bytes(address, length).iter().enumerate() {
+                        let pixels: String = (0..8)
+                            .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                            .collect();
+                        write_line(
+                            &self.syntax.byte(byte),
+                            address + offset as u16,
+                            &[byte],
+                            &pixels,
+                        )?;
+                    }
+                }
+                Line::Data { length, .. } => {
+                    let bytes = self.bytes(address, length);
+                    let text: Vec<String> =
+                        bytes.iter().map(|&byte| self.syntax.byte(byte)).collect();
+                    let note = if length == 2 { "unknown opcode" } else { "" };
+                    write_line(&text.join(" "), address, bytes, note)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_disassemble_octo() {
+    // 0x200: I = 0x20A, draw 2 rows, call 0x208, jump 0x200, 0x208: return, 0x20A: sprite
+    let program = [
+        0xA2, 0x0A, 0xD0, 0x12, 0x22, 0x08, 0x12, 0x00, 0x00, 0xEE, 0x3C, 0x81,
+    ];
+    let disassembly = Disassembly::new(&program, Syntax::Octo);
+    assert_eq!(disassembly.sprites().len(), 1);
+    assert_eq!(disassembly.sprites()[0], 0x20A..0x20C);
+    assert_eq!(disassembly.label(0x208).as_deref(), Some("sub_208"));
+
+    let expected = [
+        "    : label_200                     # 200",
+        "    i := sprite_20A                 # 200  A20A",
+        "    sprite v0 v1 2                  # 202  D012",
+        "    sub_208                         # 204  2208",
+        "    jump label_200                  # 206  1200",
+        "    : sub_208                       # 208",
+        "    return                          # 208  00EE",
+        "    : sprite_20A                    # 20A",
+        "    0x3C                            # 20A  3C      ..####..",
+        "    0x81                            # 20B  81      #......#",
+    ];
+    assert_eq!(
+        disassembly.to_string().lines().collect::<Vec<_>>(),
+        expected
+    );
+}
+#[test]
+fn test_disassemble_cowgod() {
+    // V3 = 0x10, skip if V3 != V4, unknown opcode, I = long 0x0300
+    let program = [0x63, 0x10, 0x93, 0x40, 0xE1, 0xFF, 0xF0, 0x00, 0x03, 0x00];
+    let text = Disassembly::new(&program, Syntax::Cowgod).to_string();
+    assert!(text.contains("LD V3, 0x10 "));
+    assert!(text.contains("SNE V3, V4 "));
+    assert!(text.contains("DB 0xE1 DB 0xFF"));
+    assert!(text.contains("LD I, LONG 0x300 "));
+    assert!(text.contains("; 206  F0000300"));
+}
+#[test]
+fn test_disassemble_included_programs() {
+    for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/roms")).unwrap() {
+        let program = std::fs::read(entry.unwrap().path()).unwrap();
+        let disassembly = Disassembly::new(&program, Syntax::Octo);
+        let size: u16 = disassembly.lines().iter().map(Line::size).sum();
+        assert_eq!(size as usize, program.len());
+    }
+}
+#[test]
+fn test_disassemble_to_end_of_address_space() {
+    let program = vec![0x12; XO_CHIP_MEMORY_SIZE - DEFAULT_PROGRAM_START + 1];
+    let disassembly = Disassembly::new(&program, Syntax::Octo);
+    let last_line = disassembly.lines().last().unwrap();
+    assert_eq!(last_line.address(), 0xFFFE);
+    assert_eq!(last_line.size(), 2);
+}
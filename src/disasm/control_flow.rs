@@ -0,0 +1,464 @@
+use crate::disasm::Syntax;
+use crate::instruction::Instruction;
+use crate::interpreter::builder::{DEFAULT_PROGRAM_START, XO_CHIP_MEMORY_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Why control can pass from one [BasicBlock] to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    FallThrough,
+    /// A `1nnn`.
+    Jump,
+    /// A `2nnn`. The caller also falls through to the instruction after the call.
+    Call,
+    /// A skip instruction jumps over the next instruction.
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    pub target: u16,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is always executed from the first to the last.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BasicBlock {
+    /// The address of the first instruction.
+    pub start: u16,
+    /// The address after the last instruction.
+    pub end: u16,
+    pub instructions: Vec<(u16, Instruction)>,
+    /// The blocks control can pass to after the last instruction.
+    /// Empty after a return, an exit or an indirect jump.
+    pub successors: Vec<Edge>,
+}
+
+/// A reachable instruction that writes to memory that holds reachable code.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelfModifyingWrite {
+    /// The address of the `Fx33`, `Fx55` or `5xy2`.
+    pub address: u16,
+    /// The memory that is written, based on the last `Annn` of the same block.
+    pub target: Range<u16>,
+}
+
+/// The control flow graph of the code reachable from the start of a program.
+///
+/// Code is found by recursive descent: jumps, calls and both sides of every skip are followed,
+/// subroutines are assumed to return. `Bnnn` jumps depend on `V0` and are reported instead of followed.
+/// Writes to code are found by tracking the `I` set by `Annn` within a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    program_start: u16,
+    program_data: Vec<u8>,
+    blocks: BTreeMap<u16, BasicBlock>,
+    unreachable_ranges: Vec<Range<usize>>,
+    self_modifying_writes: Vec<SelfModifyingWrite>,
+    indirect_jumps: Vec<u16>,
+    undecodable_addresses: Vec<u16>,
+}
+
+// initialization
+impl ControlFlowGraph {
+    /// Analyzes a program loaded at [DEFAULT_PROGRAM_START].
+    pub fn new(program_data: &[u8]) -> Self {
+        Self::with_program_start(program_data, DEFAULT_PROGRAM_START as u16)
+    }
+
+    /// Bytes that would be loaded past the 16 bit address space are left out.
+    pub fn with_program_start(program_data: &[u8], program_start: u16) -> Self {
+        let length = program_data
+            .len()
+            .min(XO_CHIP_MEMORY_SIZE - program_start as usize);
+        let mut graph = Self {
+            program_start,
+            program_data: program_data[..length].to_vec(),
+            blocks: BTreeMap::new(),
+            unreachable_ranges: Vec::new(),
+            self_modifying_writes: Vec::new(),
+            indirect_jumps: Vec::new(),
+            undecodable_addresses: Vec::new(),
+        };
+        let (instructions, leaders, branches) = graph.explore();
+        graph.blocks = build_blocks(&instructions, &leaders, &branches);
+        graph.unreachable_ranges = graph.find_unreachable_ranges();
+        graph.self_modifying_writes = graph.find_self_modifying_writes();
+        graph
+    }
+}
+
+// accessors
+impl ControlFlowGraph {
+    pub const fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    /// The basic blocks by their start address.
+    pub const fn blocks(&self) -> &BTreeMap<u16, BasicBlock> {
+        &self.blocks
+    }
+
+    /// Returns the block that contains the instruction at `address`.
+    pub fn block_containing(&self, address: u16) -> Option<&BasicBlock> {
+        let (_, block) = self.blocks.range(..=address).next_back()?;
+        (address < block.end).then_some(block)
+    }
+
+    /// The bytes of the program that are never executed. Usually sprites and other data.
+    pub fn unreachable_ranges(&self) -> &[Range<usize>] {
+        &self.unreachable_ranges
+    }
+
+    pub fn self_modifying_writes(&self) -> &[SelfModifyingWrite] {
+        &self.self_modifying_writes
+    }
+
+    /// The addresses of reachable `Bnnn` instructions, whose targets are unknown.
+    pub fn indirect_jumps(&self) -> &[u16] {
+        &self.indirect_jumps
+    }
+
+    /// Reachable addresses that do not hold a known instruction or are outside of the program.
+    /// Instructions that end at the top of the 16 bit address space are also reported here.
+    /// A program that reaches one of these fails with an [ExecutionError](crate::ExecutionError).
+    pub fn undecodable_addresses(&self) -> &[u16] {
+        &self.undecodable_addresses
+    }
+
+    /// The address after the last byte of the program. Up to `0x10000`, so it does not always fit in 16 bits.
+    fn program_end(&self) -> usize {
+        self.program_start as usize + self.program_data.len()
+    }
+
+    fn opcode(&self, address: u16) -> Option<u16> {
+        let offset = address.checked_sub(self.program_start)? as usize;
+        let bytes = self.program_data.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Decodes the instruction at `address` if it is completely inside of the program
+    /// and the address after it still fits in 16 bits.
+    fn decode(&self, address: u16) -> Option<Instruction> {
+        let instruction = Instruction::decode(self.opcode(address)?).ok()?;
+        let end = address.checked_add(instruction.size())?;
+        (end as usize <= self.program_end()).then_some(instruction)
+    }
+
+    /// Returns every reachable instruction, the addresses that must start a block
+    /// and the edges of the instructions that end a block.
+    #[allow(clippy::type_complexity)]
+    fn explore(
+        &mut self,
+    ) -> (
+        BTreeMap<u16, Instruction>,
+        BTreeSet<u16>,
+        BTreeMap<u16, Vec<Edge>>,
+    ) {
+        use Instruction::*;
+        let mut instructions = BTreeMap::new();
+        let mut leaders = BTreeSet::from([self.program_start]);
+        let mut branches = BTreeMap::new();
+        let mut undecodable_addresses = BTreeSet::new();
+
+        let mut worklist = vec![self.program_start];
+        while let Some(mut address) = worklist.pop() {
+            while !instructions.contains_key(&address) {
+                let Some(instruction) = self.decode(address) else {
+                    undecodable_addresses.insert(address);
+                    break;
+                };
+                instructions.insert(address, instruction);
+                let next = address + instruction.size();
+
+                let edge = |target, kind| Edge { target, kind };
+                let successors = match instruction {
+                    Jump { nnn } => vec![edge(nnn, EdgeKind::Jump)],
+                    CallSubroutine { nnn } => {
+                        vec![edge(nnn, EdgeKind::Call), edge(next, EdgeKind::FallThrough)]
+                    }
+                    SkipIfEqualValue { .. }
+                    | SkipIfNotEqualValue { .. }
+                    | SkipIfEqual { .. }
+                    | SkipIfNotEqual { .. }
+                    | SkipOnKeyPressed { .. }
+                    | SkipOnKeyNotPressed { .. } => {
+                        // skips jump over both halves of a four byte `F000 nnnn`,
+                        // and wrap around at the end of memory like the program counter
+                        let skipped_size = self.decode(next).map_or(2, Instruction::size);
+                        vec![
+                            edge(next, EdgeKind::FallThrough),
+                            edge(next.wrapping_add(skipped_size), EdgeKind::Skip),
+                        ]
+                    }
+                    JumpOffset { .. } => {
+                        self.indirect_jumps.push(address);
+                        vec![]
+                    }
+                    ReturnSubroutine | Exit => vec![],
+                    _ => {
+                        address = next;
+                        continue;
+                    }
+                };
+
+                for successor in &successors {
+                    leaders.insert(successor.target);
+                    worklist.push(successor.target);
+                }
+                branches.insert(address, successors);
+                break;
+            }
+        }
+
+        self.indirect_jumps.sort_unstable();
+        self.undecodable_addresses = undecodable_addresses.into_iter().collect();
+        (instructions, leaders, branches)
+    }
+
+    fn find_unreachable_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut address = self.program_start as usize;
+        for block in self.blocks.values() {
+            if address < block.start as usize {
+                ranges.push(address..block.start as usize);
+            }
+            address = address.max(block.end as usize);
+        }
+        if address < self.program_end() {
+            ranges.push(address..self.program_end());
+        }
+        ranges
+    }
+
+    fn find_self_modifying_writes(&self) -> Vec<SelfModifyingWrite> {
+        use Instruction::*;
+        let is_code = |target: &Range<u16>| {
+            self.blocks
+                .values()
+                .any(|block| target.start < block.end && block.start < target.end)
+        };
+
+        let mut writes = Vec::new();
+        for block in self.blocks.values() {
+            let mut address_register = None;
+            for &(address, instruction) in &block.instructions {
+                let written_length = match instruction {
+                    AddressRegisterAssign { nnn } => {
+                        address_register = Some(nnn);
+                        continue;
+                    }
+                    AddressRegisterAssignLong => {
+                        address_register = address.checked_add(2).and_then(|a| self.opcode(a));
+                        continue;
+                    }
+                    AddressRegisterAddAssign { .. }
+                    | AddressRegisterAssignCharacterAddress { .. }
+                    | AddressRegisterAssignBigCharacterAddress { .. }
+                    | LoadVariableRegisters { .. } => {
+                        address_register = None;
+                        continue;
+                    }
+                    StoreBinaryCodedDecimalAddress { .. } => 3,
+                    StoreVariableRegisters { x } => x as u16 + 1,
+                    StoreRegisterRange { x, y } => x.abs_diff(y) as u16 + 1,
+                    _ => continue,
+                };
+
+                if let Some(start) = address_register {
+                    let target = start..start.saturating_add(written_length);
+                    if is_code(&target) {
+                        writes.push(SelfModifyingWrite { address, target });
+                    }
+                }
+                // some interpreters increment `I` after saving registers
+                if let StoreVariableRegisters { .. } = instruction {
+                    address_register = None;
+                }
+            }
+        }
+        writes
+    }
+}
+
+// encoding
+impl ControlFlowGraph {
+    /// Writes the graph in the Graphviz DOT language with one node per basic block.
+    ///
+    /// Reachable addresses without an instruction are drawn as red nodes and indirect jumps as dashed edges to `?`.
+    pub fn to_dot(&self, syntax: Syntax) -> String {
+        let mut dot = String::from("digraph chip8 {\n    node [shape=box fontname=monospace]\n");
+        let node = |address: u16| format!("\"{address:03X}\"");
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for &(address, instruction) in &block.instructions {
+                let long_address = address
+                    .checked_add(2)
+                    .and_then(|a| self.opcode(a))
+                    .unwrap_or_default();
+                let text = syntax.format_instruction(instruction, long_address, |target| {
+                    format!("{target:#05X}")
+                });
+                let _ = write!(label, "{address:03X}  {}\\l", text.replace('"', "\\\""));
+            }
+            let _ = writeln!(dot, "    {} [label=\"{label}\"]", node(block.start));
+
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::FallThrough => "",
+                    EdgeKind::Jump => " [label=jump]",
+                    EdgeKind::Call => " [label=call]",
+                    EdgeKind::Skip => " [label=skip]",
+                };
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {}{style}",
+                    node(block.start),
+                    node(edge.target)
+                );
+            }
+            let last_address = block.instructions.last().map(|&(address, _)| address);
+            if last_address.is_some_and(|address| self.indirect_jumps.contains(&address)) {
+                let _ = writeln!(dot, "    {} -> \"?\" [style=dashed]", node(block.start));
+            }
+        }
+
+        if !self.indirect_jumps.is_empty() {
+            dot.push_str("    \"?\" [shape=circle]\n");
+        }
+        for &address in &self.undecodable_addresses {
+            let _ = writeln!(
+                dot,
+                "    {} [label=\"{address:03X}  not an instruction\" color=red]",
+                node(address)
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Splits the reachable instructions into blocks at every leader and after every branch.
+fn build_blocks(
+    instructions: &BTreeMap<u16, Instruction>,
+    leaders: &BTreeSet<u16>,
+    branches: &BTreeMap<u16, Vec<Edge>>,
+) -> BTreeMap<u16, BasicBlock> {
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<BasicBlock> = None;
+    for (&address, &instruction) in instructions {
+        let continues_block = current.as_ref().is_some_and(|block| {
+            block.end == address
+                && !leaders.contains(&address)
+                && !branches.contains_key(&block.instructions.last().unwrap().0)
+        });
+        if !continues_block {
+            if let Some(mut block) = current.take() {
+                if !branches.contains_key(&block.instructions.last().unwrap().0) {
+                    block.successors.push(Edge {
+                        target: block.end,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+                blocks.insert(block.start, block);
+            }
+            current = Some(BasicBlock {
+                start: address,
+                end: address,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+            });
+        }
+
+        let block = current.as_mut().unwrap();
+        block.instructions.push((address, instruction));
+        block.end = address + instruction.size();
+        if let Some(successors) = branches.get(&address) {
+            block.successors = successors.clone();
+        }
+    }
+    if let Some(mut block) = current {
+        if !branches.contains_key(&block.instructions.last().unwrap().0) {
+            block.successors.push(Edge {
+                target: block.end,
+                kind: EdgeKind::FallThrough,
+            });
+        }
+        blocks.insert(block.start, block);
+    }
+    blocks
+}
+
+#[test]
+fn test_control_flow_graph() {
+    #[rustfmt::skip]
+    let program = [
+        // 0x200: call 0x20C, skip if V0 == 1, jump 0x200, jump0 0x210
+        0x22, 0x0C, 0x30, 0x01, 0x12, 0x00, 0xB2, 0x10,
+        // 0x208: unreachable
+        0xFF, 0xFF, 0xFF, 0xFF,
+        // 0x20C: I = 0x200, save V0..V1 over the call, return
+        0xA2, 0x00, 0xF1, 0x55, 0x00, 0xEE,
+    ];
+    let graph = ControlFlowGraph::new(&program);
+
+    let starts: Vec<u16> = graph.blocks().keys().copied().collect();
+    assert_eq!(starts, [0x200, 0x202, 0x204, 0x206, 0x20C]);
+    let skip = &graph.blocks()[&0x202];
+    assert_eq!(
+        skip.successors[1],
+        Edge {
+            target: 0x206,
+            kind: EdgeKind::Skip
+        }
+    );
+    assert!(graph.blocks()[&0x20C].successors.is_empty());
+    assert_eq!(graph.block_containing(0x20E).unwrap().start, 0x20C);
+
+    assert_eq!(graph.unreachable_ranges().len(), 1);
+    assert_eq!(graph.unreachable_ranges()[0], 0x208..0x20C);
+    assert_eq!(graph.indirect_jumps(), [0x206]);
+    assert_eq!(
+        graph.self_modifying_writes(),
+        [SelfModifyingWrite {
+            address: 0x20E,
+            target: 0x200..0x202
+        }]
+    );
+
+    let dot = graph.to_dot(Syntax::Octo);
+    assert!(dot.contains("\"200\" -> \"20C\" [label=call]"));
+    assert!(dot.contains("\"206\" -> \"?\" [style=dashed]"));
+}
+#[test]
+fn test_control_flow_graph_undecodable() {
+    // skip if V0 == 0, jump 0x300, V0 = 1, unknown opcode
+    let graph = ControlFlowGraph::new(&[0x30, 0x00, 0x13, 0x00, 0x60, 0x01, 0xE0, 0xFF]);
+    assert_eq!(graph.undecodable_addresses(), [0x206, 0x300]);
+    assert!(graph
+        .to_dot(Syntax::Cowgod)
+        .contains("\"300\" [label=\"300  not an instruction\" color=red]"));
+}
+#[test]
+fn test_control_flow_graph_included_programs() {
+    for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/roms")).unwrap() {
+        let program = std::fs::read(entry.unwrap().path()).unwrap();
+        let graph = ControlFlowGraph::new(&program);
+        assert!(
+            graph.blocks().contains_key(&graph.program_start())
+                || !graph.undecodable_addresses().is_empty()
+        );
+    }
+}
+#[test]
+fn test_control_flow_graph_end_of_address_space() {
+    // 0xFFFC: skip if V0 == 0, 0xFFFE: V0 = 1, followed by an unused byte
+    let graph = ControlFlowGraph::with_program_start(&[0x30, 0x00, 0x60, 0x01, 0x00], 0xFFFC);
+    assert_eq!(graph.undecodable_addresses(), [0x0000, 0xFFFE]);
+    assert_eq!(graph.unreachable_ranges().len(), 1);
+    assert_eq!(graph.unreachable_ranges()[0], 0xFFFE..0x10000);
+}
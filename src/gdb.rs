@@ -0,0 +1,617 @@
+use crate::interpreter::{Debugger, ExecutionError, MemoryAccess, StopReason, Watchpoint};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// The largest packet the stub accepts, advertised to the client in `qSupported`.
+const PACKET_SIZE: usize = 0x1000;
+
+/// The size in bytes of every register in the order of [target_description]: `V0` to `VF`, `I`, `PC`, `SP`, `DT` and `ST`.
+const REGISTER_SIZES: [usize; 21] = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 1, 1, 1,
+];
+
+/// The signals of stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Returns the target description XML of the CHIP-8 registers, read by clients through `qXfer:features:read`.
+///
+/// Registers are numbered in order: `v0` to `vf` (0 to 15), `i`, `pc`, `sp` (the call stack depth), `dt` and `st`.
+/// Like memory, the 16 bit registers are big-endian, so clients like GDB need `set endian big`.
+pub fn target_description() -> String {
+    let mut registers = String::new();
+    for index in 0..16 {
+        registers.push_str(&format!(
+            "    <reg name=\"v{index:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{index}\"/>\n"
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\"?>
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">
+<target version=\"1.0\">
+  <feature name=\"org.chip8.core\">
+{registers}    <reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>
+    <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>
+    <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>
+    <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>
+    <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>
+  </feature>
+</target>
+"
+    )
+}
+
+/// Serves the GDB Remote Serial Protocol over TCP so a debugger client can attach to a [Debugger].
+///
+/// Supports reading and writing registers and memory, software breakpoints (`Z0`),
+/// watchpoints (`Z2` to `Z4`), single steps, continuing and interrupting with Ctrl-C.
+/// Continuing runs frames as fast as possible until something stops execution.
+#[derive(Debug)]
+pub struct GdbStub {
+    debugger: Debugger,
+}
+
+// initialization
+impl GdbStub {
+    pub fn new(debugger: Debugger) -> Self {
+        Self { debugger }
+    }
+}
+
+// accessors
+impl GdbStub {
+    pub const fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn into_debugger(self) -> Debugger {
+        self.debugger
+    }
+
+    /// Returns the bytes of register `number` in the order of [target_description].
+    fn read_register(&self, number: usize) -> Option<Vec<u8>> {
+        let chip8 = self.debugger.interpreter();
+        let bytes = match number {
+            0..=15 => vec![chip8.registers()[number]],
+            16 => chip8.address_register().to_be_bytes().to_vec(),
+            17 => chip8.program_counter().to_be_bytes().to_vec(),
+            18 => vec![chip8.call_stack().len() as u8],
+            19 => vec![chip8.delay_timer()],
+            20 => vec![chip8.sound_timer()],
+            _ => return None,
+        };
+        Some(bytes)
+    }
+
+    fn read_registers(&self) -> Vec<u8> {
+        (0..REGISTER_SIZES.len())
+            .filter_map(|number| self.read_register(number))
+            .flatten()
+            .collect()
+    }
+
+    /// Returns the reply to a stop of execution.
+    fn stop_reply(&self, stop_reason: &StopReason) -> String {
+        match stop_reason {
+            StopReason::Breakpoint { .. } => format!("T{SIGTRAP:02x}swbreak:;"),
+            StopReason::Watchpoint { access, .. } => self.watchpoint_reply(access),
+            StopReason::Step | StopReason::FrameComplete | StopReason::WaitingForKey => {
+                format!("S{SIGTRAP:02x}")
+            }
+            StopReason::Exited => "W00".to_owned(),
+            StopReason::Error(
+                ExecutionError::UnknownOpcode { .. } | ExecutionError::AssemblyRoutine { .. },
+            ) => format!("S{SIGILL:02x}"),
+            StopReason::Error(_) => format!("S{SIGSEGV:02x}"),
+        }
+    }
+
+    /// Returns `watch`, `rwatch` or `awatch` with the first watched address that was accessed.
+    fn watchpoint_reply(&self, access: &MemoryAccess) -> String {
+        let Some(watchpoint) = self
+            .debugger
+            .watchpoints()
+            .iter()
+            .find(|watchpoint| watchpoint.matches(access))
+        else {
+            return format!("S{SIGTRAP:02x}");
+        };
+        let kind = match (watchpoint.on_read, watchpoint.on_write) {
+            (true, true) => "awatch",
+            (true, false) => "rwatch",
+            _ => "watch",
+        };
+        let address = access.range.start.max(watchpoint.range.start);
+        format!("T{SIGTRAP:02x}{kind}:{address:x};")
+    }
+}
+
+// mutators
+impl GdbStub {
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// Waits for one client to connect to `address` and serves it until it detaches or disconnects.
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let (stream, _) = TcpListener::bind(address)?.accept()?;
+        self.serve(stream)
+    }
+
+    /// Serves a connected client until it detaches (`D`), kills the program (`k`) or disconnects.
+    pub fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut connection = Connection {
+            stream,
+            received: VecDeque::new(),
+            acknowledge: true,
+        };
+        loop {
+            let packet = match connection.read_packet() {
+                Ok(packet) => packet,
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(error) => return Err(error),
+            };
+            // an interrupt while already stopped is answered with the current stop
+            let command = packet.unwrap_or_else(|| "?".to_owned());
+            match command.as_str() {
+                "k" => return Ok(()),
+                "D" => return connection.send_packet("OK"),
+                "QStartNoAckMode" => {
+                    connection.send_packet("OK")?;
+                    connection.acknowledge = false;
+                }
+                _ => {
+                    let reply = self.handle_command(&command, &mut connection)?;
+                    connection.send_packet(&reply)?;
+                }
+            }
+        }
+    }
+
+    /// Returns the reply to `command`. Unsupported commands get the empty reply.
+    fn handle_command(&mut self, command: &str, connection: &mut Connection) -> io::Result<String> {
+        let (kind, arguments) = command.split_at(command.chars().next().map_or(0, char::len_utf8));
+        let reply = match kind {
+            "?" => format!("S{SIGTRAP:02x}"),
+            "g" => to_hex(&self.read_registers()),
+            "G" => reply_ok(self.write_registers(arguments)),
+            "p" => usize::from_str_radix(arguments, 16)
+                .ok()
+                .and_then(|number| self.read_register(number))
+                .map_or_else(|| "E00".to_owned(), |bytes| to_hex(&bytes)),
+            "P" => reply_ok(arguments.split_once('=').and_then(|(number, value)| {
+                let number = usize::from_str_radix(number, 16).ok()?;
+                self.write_register(number, &from_hex(value)?)
+            })),
+            "m" => self.read_memory(arguments),
+            "M" => reply_ok(self.write_memory(arguments)),
+            "Z" => reply_ok(self.change_stop_point(arguments, true)),
+            "z" => reply_ok(self.change_stop_point(arguments, false)),
+            "s" | "c" => {
+                if !arguments.is_empty() {
+                    let moved = u16::from_str_radix(arguments, 16).ok().and_then(|address| {
+                        let chip8 = self.debugger.interpreter_mut();
+                        chip8.set_program_counter(address).ok()
+                    });
+                    if moved.is_none() {
+                        return Ok("E01".to_owned());
+                    }
+                }
+                let stop_reason = match kind {
+                    "s" => self.debugger.step_into(),
+                    _ => match self.continue_execution(connection)? {
+                        Some(stop_reason) => stop_reason,
+                        None => return Ok(format!("S{SIGINT:02x}")),
+                    },
+                };
+                self.stop_reply(&stop_reason)
+            }
+            "H" | "T" => "OK".to_owned(),
+            "q" => query_reply(arguments),
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    /// Runs frames until execution stops. Returns `None` if the client interrupted it.
+    fn continue_execution(
+        &mut self,
+        connection: &mut Connection,
+    ) -> io::Result<Option<StopReason>> {
+        loop {
+            match self.debugger.run_frame() {
+                StopReason::FrameComplete => {
+                    if connection.is_interrupted()? {
+                        return Ok(None);
+                    }
+                }
+                stop_reason => return Ok(Some(stop_reason)),
+            }
+        }
+    }
+
+    fn write_register(&mut self, number: usize, bytes: &[u8]) -> Option<()> {
+        if bytes.len() != *REGISTER_SIZES.get(number)? {
+            return None;
+        }
+        let chip8 = self.debugger.interpreter_mut();
+        let word = || u16::from_be_bytes([bytes[0], bytes[1]]);
+        match number {
+            0..=15 => chip8.registers_mut()[number] = bytes[0],
+            16 => chip8.set_address_register(word()),
+            17 => chip8.set_program_counter(word()).ok()?,
+            18 => {
+                let mut call_stack = chip8.call_stack().to_vec();
+                call_stack.resize(bytes[0] as usize, 0);
+                chip8.set_call_stack(&call_stack).ok()?;
+            }
+            19 => chip8.set_delay_timer(bytes[0]),
+            _ => chip8.set_sound_timer(bytes[0]),
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Option<()> {
+        let bytes = from_hex(hex)?;
+        if bytes.len() != REGISTER_SIZES.iter().sum::<usize>() {
+            return None;
+        }
+        let mut offset = 0;
+        for (number, size) in REGISTER_SIZES.into_iter().enumerate() {
+            self.write_register(number, &bytes[offset..offset + size])?;
+            offset += size;
+        }
+        Some(())
+    }
+
+    /// `m addr,length`
+    fn read_memory(&self, arguments: &str) -> String {
+        let memory = self.debugger.interpreter().memory();
+        parse_address_and_length(arguments)
+            .filter(|&(_, length)| length <= PACKET_SIZE / 2)
+            .and_then(|(address, length)| memory.get(address..address.checked_add(length)?))
+            .map_or_else(|| "E01".to_owned(), to_hex)
+    }
+
+    /// `M addr,length:XX...`
+    fn write_memory(&mut self, arguments: &str) -> Option<()> {
+        let (location, hex) = arguments.split_once(':')?;
+        let (address, length) = parse_address_and_length(location)?;
+        address.checked_add(length)?;
+        let bytes = from_hex(hex).filter(|bytes| bytes.len() == length)?;
+        let chip8 = self.debugger.interpreter_mut();
+        chip8.write_memory(address, &bytes).ok()
+    }
+
+    /// `Z type,addr,kind` inserts and `z type,addr,kind` removes a breakpoint or watchpoint.
+    /// For watchpoints `kind` is the number of bytes watched.
+    fn change_stop_point(&mut self, arguments: &str, insert: bool) -> Option<()> {
+        let (kind, location) = arguments.split_once(',')?;
+        let (address, length) = parse_address_and_length(location)?;
+        let watchpoint = match kind {
+            "0" | "1" => {
+                let address = u16::try_from(address).ok()?;
+                if insert {
+                    self.debugger.add_breakpoint(address);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                return Some(());
+            }
+            "2" => Watchpoint::write(address..address.checked_add(length)?),
+            "3" => Watchpoint::read(address..address.checked_add(length)?),
+            "4" => Watchpoint::access(address..address.checked_add(length)?),
+            _ => return None,
+        };
+        if insert {
+            self.debugger.add_watchpoint(watchpoint);
+        } else {
+            self.debugger.remove_watchpoint(&watchpoint);
+        }
+        Some(())
+    }
+}
+
+/// Returns the reply to the `q` queries. Unsupported queries get the empty reply.
+fn query_reply(query: &str) -> String {
+    if query.starts_with("Supported") {
+        return format!(
+            "PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+;swbreak+"
+        );
+    }
+    if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+        let description = target_description();
+        let Some((offset, length)) = parse_address_and_length(range) else {
+            return "E00".to_owned();
+        };
+        let start = offset.min(description.len());
+        let end = offset.saturating_add(length).min(description.len());
+        let marker = if end == description.len() { 'l' } else { 'm' };
+        return format!("{marker}{}", escape(&description[start..end]));
+    }
+    match query {
+        "Attached" => "1".to_owned(),
+        "C" => "QC1".to_owned(),
+        "fThreadInfo" => "m1".to_owned(),
+        "sThreadInfo" => "l".to_owned(),
+        _ => String::new(),
+    }
+}
+
+fn reply_ok(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_owned(),
+        None => "E01".to_owned(),
+    }
+}
+
+/// Reads `addr,length` with both in hexadecimal.
+fn parse_address_and_length(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// Escapes the characters with a meaning in packets as `}` followed by the character xor `0x20`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if matches!(character, '$' | '#' | '}' | '*') {
+            escaped.push('}');
+            escaped.push((character as u8 ^ 0x20) as char);
+        } else {
+            escaped.push(character);
+        }
+    }
+    escaped
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// A client connection that reads and writes packets like `$m200,2#fd`.
+struct Connection {
+    stream: TcpStream,
+    /// Bytes received but not read yet.
+    received: VecDeque<u8>,
+    /// Packets are acknowledged with `+` until the client sends `QStartNoAckMode`.
+    acknowledge: bool,
+}
+
+impl Connection {
+    fn next_byte(&mut self) -> io::Result<u8> {
+        loop {
+            if let Some(byte) = self.received.pop_front() {
+                return Ok(byte);
+            }
+            let mut buffer = [0; 1024];
+            let length = self.stream.read(&mut buffer)?;
+            if length == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.received.extend(&buffer[..length]);
+        }
+    }
+
+    /// Returns the next command, or `None` for an interrupt (Ctrl-C). Acknowledgements are skipped.
+    /// Packets larger than the advertised [PACKET_SIZE] are an `InvalidData` error.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.next_byte()? {
+                0x03 => return Ok(None),
+                b'$' => {}
+                _ => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.next_byte()? {
+                    b'#' => break,
+                    b'}' => data.push(self.next_byte()? ^ 0x20),
+                    byte => data.push(byte),
+                }
+                if data.len() > PACKET_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("the packet is larger than {PACKET_SIZE} bytes"),
+                    ));
+                }
+            }
+            let checksum_text = [self.next_byte()?, self.next_byte()?];
+            let received_checksum = std::str::from_utf8(&checksum_text)
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+            if self.acknowledge {
+                if received_checksum != Some(checksum(&data)) {
+                    self.stream.write_all(b"-")?;
+                    continue;
+                }
+                self.stream.write_all(b"+")?;
+            }
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    /// Sends `data` and, unless acknowledgements are off, resends it until the client acknowledges it.
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if !self.acknowledge {
+                return Ok(());
+            }
+            match self.next_byte()? {
+                b'-' => continue,
+                b'+' => return Ok(()),
+                byte => {
+                    self.received.push_front(byte);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the client sent an interrupt (Ctrl-C), without waiting for one.
+    fn is_interrupted(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        self.stream.set_nonblocking(true)?;
+        let result = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(length) => self.received.extend(&buffer[..length]),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error),
+        }
+        let Some(index) = self.received.iter().position(|&byte| byte == 0x03) else {
+            return Ok(false);
+        };
+        self.received.remove(index);
+        Ok(true)
+    }
+}
+
+/// A scripted client for the tests.
+#[cfg(test)]
+struct TestClient {
+    stream: TcpStream,
+    acknowledge: bool,
+}
+#[cfg(test)]
+impl TestClient {
+    fn send(&mut self, command: &str) {
+        let packet = format!("${command}#{:02x}", checksum(command.as_bytes()));
+        self.stream.write_all(packet.as_bytes()).unwrap();
+        if self.acknowledge {
+            assert_eq!(self.read_byte(), b'+');
+        }
+    }
+
+    fn receive(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum_text = [self.read_byte(), self.read_byte()];
+        let expected = format!("{:02x}", checksum(&data));
+        assert_eq!(checksum_text, expected.as_bytes());
+        if self.acknowledge {
+            self.stream.write_all(b"+").unwrap();
+        }
+        String::from_utf8(data).unwrap()
+    }
+
+    fn request(&mut self, command: &str) -> String {
+        self.send(command);
+        self.receive()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn test_gdb_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut interpreter = crate::Interpreter::default();
+        // v3 := 0x10, i := 0x300, save v3, jump 0x206
+        interpreter
+            .load_program_from_bytes([0x63, 0x10, 0xA3, 0x00, 0xF3, 0x55, 0x12, 0x06])
+            .unwrap();
+        let mut stub = GdbStub::new(Debugger::new(interpreter));
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream).unwrap();
+        stub.into_debugger().into_interpreter()
+    });
+    let mut client = TestClient {
+        stream: TcpStream::connect(address).unwrap(),
+        acknowledge: true,
+    };
+
+    assert!(client
+        .request("qSupported:swbreak+")
+        .contains("qXfer:features:read+"));
+    let description = client.request("qXfer:features:read:target.xml:0,fff");
+    assert!(description.starts_with("l<?xml"));
+    assert!(description.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+    assert_eq!(client.request("?"), "S05");
+    let registers = format!("{}0000020000{}", "00".repeat(16), "0000");
+    assert_eq!(client.request("g"), registers);
+
+    assert_eq!(client.request("Z0,204,2"), "OK");
+    assert_eq!(client.request("c"), "T05swbreak:;");
+    assert_eq!(client.request("p3"), "10");
+    assert_eq!(client.request("p11"), "0204");
+    assert_eq!(client.request("z0,204,2"), "OK");
+
+    assert_eq!(client.request("Z2,303,1"), "OK");
+    assert_eq!(client.request("c"), "T05watch:303;");
+    assert_eq!(client.request("m300,4"), "00000010");
+    assert_eq!(client.request("z2,303,1"), "OK");
+    assert_eq!(client.request("Z2,ffffffffffffffff,2"), "E01");
+
+    assert_eq!(client.request("P3=2a"), "OK");
+    assert_eq!(client.request("M400,2:abcd"), "OK");
+    assert_eq!(client.request("m400,2"), "abcd");
+    assert_eq!(client.request("mffff,2"), "E01");
+    assert_eq!(client.request("Mffffffffffffffff,1:00"), "E01");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0206");
+
+    client.send("c");
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.receive(), "S02");
+
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    client.acknowledge = false;
+    assert_eq!(client.request("vMustReplyEmpty"), "");
+    assert_eq!(client.request("D"), "OK");
+
+    let interpreter = server.join().unwrap();
+    assert_eq!(interpreter.registers()[3], 0x2A);
+    assert_eq!(interpreter.memory()[0x400..0x402], [0xAB, 0xCD]);
+}
+#[test]
+fn test_gdb_packet_too_large() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut stub = GdbStub::new(Debugger::new(crate::Interpreter::default()));
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream)
+    });
+    let mut stream = TcpStream::connect(address).unwrap();
+    let packet = format!("${}", "0".repeat(PACKET_SIZE + 1));
+    stream.write_all(packet.as_bytes()).unwrap();
+    let error = server.join().unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,359 @@
+use crate::nibbles::{
+    concatenate_three_nibbles, concatenate_two_nibbles, get_first_nibble, get_second_nibble,
+};
+
+/// The chip8 extensions an [Instruction] can belong to.
+/// Each instruction set includes every instruction of the instruction sets before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstructionSet {
+    Chip8,
+    SuperChip10,
+    SuperChip11,
+    XoChip,
+}
+
+/// A single decoded chip8 instruction.
+///
+/// Operands are named after the conventional opcode notation:
+/// - `x`: index of the `VX` register (second nibble)
+/// - `y`: index of the `VY` register (third nibble)
+/// - `nnn`: a 12 bit address (last three nibbles)
+/// - `kk`: an 8 bit value (last byte)
+/// - `n`: a 4 bit value (last nibble)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Opcode: 0nnn
+    AssemblyRoutine { nnn: u16 },
+    /// Opcode: 00E0
+    ClearDisplay,
+    /// Opcode: 00EE
+    ReturnSubroutine,
+    /// Opcode: 00Cn (SUPER-CHIP 1.1)
+    ScrollDown { n: u8 },
+    /// Opcode: 00Dn (XO-CHIP)
+    ScrollUp { n: u8 },
+    /// Opcode: 00FB (SUPER-CHIP 1.1)
+    ScrollRight,
+    /// Opcode: 00FC (SUPER-CHIP 1.1)
+    ScrollLeft,
+    /// Opcode: 00FD (SUPER-CHIP 1.0)
+    Exit,
+    /// Opcode: 00FE (SUPER-CHIP 1.0)
+    LowResolution,
+    /// Opcode: 00FF (SUPER-CHIP 1.0)
+    HighResolution,
+    /// Opcode: 1nnn
+    Jump { nnn: u16 },
+    /// Opcode: 2nnn
+    CallSubroutine { nnn: u16 },
+    /// Opcode: 3xkk
+    SkipIfEqualValue { x: u8, kk: u8 },
+    /// Opcode: 4xkk
+    SkipIfNotEqualValue { x: u8, kk: u8 },
+    /// Opcode: 5xy0
+    SkipIfEqual { x: u8, y: u8 },
+    /// Opcode: 5xy2 (XO-CHIP)
+    StoreRegisterRange { x: u8, y: u8 },
+    /// Opcode: 5xy3 (XO-CHIP)
+    LoadRegisterRange { x: u8, y: u8 },
+    /// Opcode: 6xkk
+    AssignValue { x: u8, kk: u8 },
+    /// Opcode: 7xkk
+    AddAssignValue { x: u8, kk: u8 },
+    /// Opcode: 8xy0
+    Assign { x: u8, y: u8 },
+    /// Opcode: 8xy1
+    BitwiseOrAssign { x: u8, y: u8 },
+    /// Opcode: 8xy2
+    BitwiseAndAssign { x: u8, y: u8 },
+    /// Opcode: 8xy3
+    BitwiseXorAssign { x: u8, y: u8 },
+    /// Opcode: 8xy4
+    AddAssign { x: u8, y: u8 },
+    /// Opcode: 8xy5
+    SubAssign { x: u8, y: u8 },
+    /// Opcode: 8xy6
+    RightShiftAssign { x: u8, y: u8 },
+    /// Opcode: 8xy7
+    SubAssignSwapped { x: u8, y: u8 },
+    /// Opcode: 8xyE
+    LeftShiftAssign { x: u8, y: u8 },
+    /// Opcode: 9xy0
+    SkipIfNotEqual { x: u8, y: u8 },
+    /// Opcode: Annn
+    AddressRegisterAssign { nnn: u16 },
+    /// Opcode: Bnnn
+    ///
+    /// `x` is the first nibble of `nnn`. It is only used when `VX` is the offset register.
+    JumpOffset { x: u8, nnn: u16 },
+    /// Opcode: Cxkk
+    RandomNumberAssign { x: u8, kk: u8 },
+    /// Opcode: Dxyn
+    ///
+    /// Dxy0 draws a 16x16 sprite on SUPER-CHIP 1.0 and later.
+    DrawSprite { x: u8, y: u8, n: u8 },
+    /// Opcode: Ex9E
+    SkipOnKeyPressed { x: u8 },
+    /// Opcode: ExA1
+    SkipOnKeyNotPressed { x: u8 },
+    /// Opcode: F000 nnnn (XO-CHIP)
+    ///
+    /// The 16 bit address is stored in the two bytes after the opcode, making this a four byte instruction.
+    AddressRegisterAssignLong,
+    /// Opcode: Fn01 (XO-CHIP)
+    ///
+    /// `n` is the second nibble. Each bit selects one display plane.
+    SelectPlanes { n: u8 },
+    /// Opcode: F002 (XO-CHIP)
+    StoreAudioPattern,
+    /// Opcode: Fx07
+    StoreDelayTimer { x: u8 },
+    /// Opcode: Fx0A
+    WaitForKeyPress { x: u8 },
+    /// Opcode: Fx15
+    DelayTimerAssign { x: u8 },
+    /// Opcode: Fx18
+    SoundTimerAssign { x: u8 },
+    /// Opcode: Fx1E
+    AddressRegisterAddAssign { x: u8 },
+    /// Opcode: Fx29
+    AddressRegisterAssignCharacterAddress { x: u8 },
+    /// Opcode: Fx30 (SUPER-CHIP 1.1)
+    AddressRegisterAssignBigCharacterAddress { x: u8 },
+    /// Opcode: Fx33
+    StoreBinaryCodedDecimalAddress { x: u8 },
+    /// Opcode: Fx3A (XO-CHIP)
+    PitchAssign { x: u8 },
+    /// Opcode: Fx55
+    StoreVariableRegisters { x: u8 },
+    /// Opcode: Fx65
+    LoadVariableRegisters { x: u8 },
+    /// Opcode: Fx75 (SUPER-CHIP 1.0)
+    StoreFlagRegisters { x: u8 },
+    /// Opcode: Fx85 (SUPER-CHIP 1.0)
+    LoadFlagRegisters { x: u8 },
+}
+
+/// The error returned by [Instruction::decode] when an opcode does not match any instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnknownOpcode(pub u16);
+impl std::fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown opcode {:#06X}", self.0)
+    }
+}
+impl std::error::Error for UnknownOpcode {}
+
+impl Instruction {
+    /// Converts a big-endian opcode into an [Instruction].
+    ///
+    /// # Example
+    /// ```
+    /// use chip8::Instruction;
+    /// let instruction = Instruction::decode(0xD125).unwrap();
+    /// assert_eq!(instruction, Instruction::DrawSprite { x: 0x1, y: 0x2, n: 0x5 });
+    /// ```
+    #[rustfmt::skip]
+    pub fn decode(opcode: u16) -> Result<Self, UnknownOpcode> {
+        let [most_significant_byte, least_significant_byte] = opcode.to_be_bytes();
+        let nibbles = [
+            get_first_nibble(most_significant_byte),
+            get_second_nibble(most_significant_byte),
+            get_first_nibble(least_significant_byte),
+            get_second_nibble(least_significant_byte),
+        ];
+
+        let nnn = concatenate_three_nibbles(nibbles[1], nibbles[2], nibbles[3]);
+        let kk = concatenate_two_nibbles(nibbles[2], nibbles[3]);
+        let x = nibbles[1];
+        let y = nibbles[2];
+        let n = nibbles[3];
+
+        let instruction = match nibbles {
+            [0x0, 0x0, 0xE, 0x0] => Self::ClearDisplay,
+            [0x0, 0x0, 0xE, 0xE] => Self::ReturnSubroutine,
+            [0x0, 0x0, 0xC,   _] => Self::ScrollDown { n },
+            [0x0, 0x0, 0xD,   _] => Self::ScrollUp { n },
+            [0x0, 0x0, 0xF, 0xB] => Self::ScrollRight,
+            [0x0, 0x0, 0xF, 0xC] => Self::ScrollLeft,
+            [0x0, 0x0, 0xF, 0xD] => Self::Exit,
+            [0x0, 0x0, 0xF, 0xE] => Self::LowResolution,
+            [0x0, 0x0, 0xF, 0xF] => Self::HighResolution,
+            [0x0,   _,   _,   _] => Self::AssemblyRoutine { nnn },
+            [0x1,   _,   _,   _] => Self::Jump { nnn },
+            [0x2,   _,   _,   _] => Self::CallSubroutine { nnn },
+            [0x3,   _,   _,   _] => Self::SkipIfEqualValue { x, kk },
+            [0x4,   _,   _,   _] => Self::SkipIfNotEqualValue { x, kk },
+            [0x5,   _,   _, 0x0] => Self::SkipIfEqual { x, y },
+            [0x5,   _,   _, 0x2] => Self::StoreRegisterRange { x, y },
+            [0x5,   _,   _, 0x3] => Self::LoadRegisterRange { x, y },
+            [0x6,   _,   _,   _] => Self::AssignValue { x, kk },
+            [0x7,   _,   _,   _] => Self::AddAssignValue { x, kk },
+            [0x8,   _,   _, 0x0] => Self::Assign { x, y },
+            [0x8,   _,   _, 0x1] => Self::BitwiseOrAssign { x, y },
+            [0x8,   _,   _, 0x2] => Self::BitwiseAndAssign { x, y },
+            [0x8,   _,   _, 0x3] => Self::BitwiseXorAssign { x, y },
+            [0x8,   _,   _, 0x4] => Self::AddAssign { x, y },
+            [0x8,   _,   _, 0x5] => Self::SubAssign { x, y },
+            [0x8,   _,   _, 0x6] => Self::RightShiftAssign { x, y },
+            [0x8,   _,   _, 0x7] => Self::SubAssignSwapped { x, y },
+            [0x8,   _,   _, 0xE] => Self::LeftShiftAssign { x, y },
+            [0x9,   _,   _, 0x0] => Self::SkipIfNotEqual { x, y },
+            [0xA,   _,   _,   _] => Self::AddressRegisterAssign { nnn },
+            [0xB,   _,   _,   _] => Self::JumpOffset { x, nnn },
+            [0xC,   _,   _,   _] => Self::RandomNumberAssign { x, kk },
+            [0xD,   _,   _,   _] => Self::DrawSprite { x, y, n },
+            [0xE,   _, 0x9, 0xE] => Self::SkipOnKeyPressed { x },
+            [0xE,   _, 0xA, 0x1] => Self::SkipOnKeyNotPressed { x },
+            [0xF, 0x0, 0x0, 0x0] => Self::AddressRegisterAssignLong,
+            [0xF,   _, 0x0, 0x1] => Self::SelectPlanes { n: x },
+            [0xF, 0x0, 0x0, 0x2] => Self::StoreAudioPattern,
+            [0xF,   _, 0x0, 0x7] => Self::StoreDelayTimer { x },
+            [0xF,   _, 0x0, 0xA] => Self::WaitForKeyPress { x },
+            [0xF,   _, 0x1, 0x5] => Self::DelayTimerAssign { x },
+            [0xF,   _, 0x1, 0x8] => Self::SoundTimerAssign { x },
+            [0xF,   _, 0x1, 0xE] => Self::AddressRegisterAddAssign { x },
+            [0xF,   _, 0x2, 0x9] => Self::AddressRegisterAssignCharacterAddress { x },
+            [0xF,   _, 0x3, 0x0] => Self::AddressRegisterAssignBigCharacterAddress { x },
+            [0xF,   _, 0x3, 0x3] => Self::StoreBinaryCodedDecimalAddress { x },
+            [0xF,   _, 0x3, 0xA] => Self::PitchAssign { x },
+            [0xF,   _, 0x5, 0x5] => Self::StoreVariableRegisters { x },
+            [0xF,   _, 0x6, 0x5] => Self::LoadVariableRegisters { x },
+            [0xF,   _, 0x7, 0x5] => Self::StoreFlagRegisters { x },
+            [0xF,   _, 0x8, 0x5] => Self::LoadFlagRegisters { x },
+            _ => return Err(UnknownOpcode(opcode)),
+        };
+
+        Ok(instruction)
+    }
+
+    /// Converts an [Instruction] back into its big-endian opcode.
+    ///
+    /// # Example
+    /// ```
+    /// use chip8::Instruction;
+    /// let instruction = Instruction::AddAssignValue { x: 0x3, kk: 0x10 };
+    /// assert_eq!(instruction.encode(), 0x7310);
+    /// ```
+    #[rustfmt::skip]
+    pub fn encode(self) -> u16 {
+        let xy = |prefix: u16, x: u8, y: u8, suffix: u16| {
+            (prefix << 12) | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | suffix
+        };
+        let xkk = |prefix: u16, x: u8, kk: u8| (prefix << 12) | ((x as u16 & 0xF) << 8) | kk as u16;
+        let nnn = |prefix: u16, nnn: u16| (prefix << 12) | (nnn & 0x0FFF);
+
+        match self {
+            Self::AssemblyRoutine { nnn: address }       => nnn(0x0, address),
+            Self::ClearDisplay                           => 0x00E0,
+            Self::ReturnSubroutine                       => 0x00EE,
+            Self::ScrollDown { n }                       => 0x00C0 | (n as u16 & 0xF),
+            Self::ScrollUp { n }                         => 0x00D0 | (n as u16 & 0xF),
+            Self::ScrollRight                            => 0x00FB,
+            Self::ScrollLeft                             => 0x00FC,
+            Self::Exit                                   => 0x00FD,
+            Self::LowResolution                          => 0x00FE,
+            Self::HighResolution                         => 0x00FF,
+            Self::Jump { nnn: address }                  => nnn(0x1, address),
+            Self::CallSubroutine { nnn: address }        => nnn(0x2, address),
+            Self::SkipIfEqualValue { x, kk }             => xkk(0x3, x, kk),
+            Self::SkipIfNotEqualValue { x, kk }          => xkk(0x4, x, kk),
+            Self::SkipIfEqual { x, y }                   => xy(0x5, x, y, 0x0),
+            Self::StoreRegisterRange { x, y }            => xy(0x5, x, y, 0x2),
+            Self::LoadRegisterRange { x, y }             => xy(0x5, x, y, 0x3),
+            Self::AssignValue { x, kk }                  => xkk(0x6, x, kk),
+            Self::AddAssignValue { x, kk }               => xkk(0x7, x, kk),
+            Self::Assign { x, y }                        => xy(0x8, x, y, 0x0),
+            Self::BitwiseOrAssign { x, y }               => xy(0x8, x, y, 0x1),
+            Self::BitwiseAndAssign { x, y }              => xy(0x8, x, y, 0x2),
+            Self::BitwiseXorAssign { x, y }              => xy(0x8, x, y, 0x3),
+            Self::AddAssign { x, y }                     => xy(0x8, x, y, 0x4),
+            Self::SubAssign { x, y }                     => xy(0x8, x, y, 0x5),
+            Self::RightShiftAssign { x, y }              => xy(0x8, x, y, 0x6),
+            Self::SubAssignSwapped { x, y }              => xy(0x8, x, y, 0x7),
+            Self::LeftShiftAssign { x, y }               => xy(0x8, x, y, 0xE),
+            Self::SkipIfNotEqual { x, y }                => xy(0x9, x, y, 0x0),
+            Self::AddressRegisterAssign { nnn: address } => nnn(0xA, address),
+            Self::JumpOffset { nnn: address, .. }        => nnn(0xB, address),
+            Self::RandomNumberAssign { x, kk }           => xkk(0xC, x, kk),
+            Self::DrawSprite { x, y, n }                 => xy(0xD, x, y, n as u16 & 0xF),
+            Self::SkipOnKeyPressed { x }                 => xkk(0xE, x, 0x9E),
+            Self::SkipOnKeyNotPressed { x }              => xkk(0xE, x, 0xA1),
+            Self::AddressRegisterAssignLong              => 0xF000,
+            Self::SelectPlanes { n }                     => xkk(0xF, n, 0x01),
+            Self::StoreAudioPattern                      => 0xF002,
+            Self::StoreDelayTimer { x }                  => xkk(0xF, x, 0x07),
+            Self::WaitForKeyPress { x }                  => xkk(0xF, x, 0x0A),
+            Self::DelayTimerAssign { x }                 => xkk(0xF, x, 0x15),
+            Self::SoundTimerAssign { x }                 => xkk(0xF, x, 0x18),
+            Self::AddressRegisterAddAssign { x }         => xkk(0xF, x, 0x1E),
+            Self::AddressRegisterAssignCharacterAddress { x } => xkk(0xF, x, 0x29),
+            Self::AddressRegisterAssignBigCharacterAddress { x } => xkk(0xF, x, 0x30),
+            Self::StoreBinaryCodedDecimalAddress { x }   => xkk(0xF, x, 0x33),
+            Self::PitchAssign { x }                      => xkk(0xF, x, 0x3A),
+            Self::StoreVariableRegisters { x }           => xkk(0xF, x, 0x55),
+            Self::LoadVariableRegisters { x }            => xkk(0xF, x, 0x65),
+            Self::StoreFlagRegisters { x }               => xkk(0xF, x, 0x75),
+            Self::LoadFlagRegisters { x }                => xkk(0xF, x, 0x85),
+        }
+    }
+
+    /// Returns the first [InstructionSet] that includes this instruction.
+    pub const fn instruction_set(self) -> InstructionSet {
+        match self {
+            Self::Exit
+            | Self::LowResolution
+            | Self::HighResolution
+            | Self::StoreFlagRegisters { .. }
+            | Self::LoadFlagRegisters { .. } => InstructionSet::SuperChip10,
+            Self::ScrollDown { .. }
+            | Self::ScrollRight
+            | Self::ScrollLeft
+            | Self::AddressRegisterAssignBigCharacterAddress { .. } => InstructionSet::SuperChip11,
+            Self::ScrollUp { .. }
+            | Self::StoreRegisterRange { .. }
+            | Self::LoadRegisterRange { .. }
+            | Self::AddressRegisterAssignLong
+            | Self::SelectPlanes { .. }
+            | Self::StoreAudioPattern
+            | Self::PitchAssign { .. } => InstructionSet::XoChip,
+            _ => InstructionSet::Chip8,
+        }
+    }
+
+    /// Returns the number of bytes this instruction takes up in memory.
+    ///
+    /// # Example
+    /// ```
+    /// use chip8::Instruction;
+    /// assert_eq!(Instruction::ClearDisplay.size(), 2);
+    /// assert_eq!(Instruction::AddressRegisterAssignLong.size(), 4);
+    /// ```
+    pub const fn size(self) -> u16 {
+        match self {
+            Self::AddressRegisterAssignLong => 4,
+            _ => 2,
+        }
+    }
+}
+
+#[test]
+fn test_decode_encode_round_trip() {
+    for opcode in 0..=u16::MAX {
+        if let Ok(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{instruction:?}");
+        }
+    }
+}
+#[test]
+fn test_decode_unknown_opcode() {
+    assert_eq!(Instruction::decode(0x5121), Err(UnknownOpcode(0x5121)));
+    assert_eq!(Instruction::decode(0xE1FF), Err(UnknownOpcode(0xE1FF)));
+    assert_eq!(Instruction::decode(0xF1FF), Err(UnknownOpcode(0xF1FF)));
+}
+#[test]
+fn test_decode_operands() {
+    let instruction = Instruction::decode(0xB2A4).unwrap();
+    let expected = Instruction::JumpOffset { x: 0x2, nnn: 0x2A4 };
+    assert_eq!(instruction, expected);
+}
@@ -1,11 +1,54 @@
-pub use crate::interpreter::builder::{ConfigurationBuilder, Configuration};
-use crate::nibbles::{
-    concatenate_three_nibbles, concatenate_two_nibbles, get_first_nibble, get_second_nibble,
+use crate::disasm::Syntax;
+use crate::instruction::{Instruction, InstructionSet};
+use crate::interpreter::builder::TIMER_INTERVAL;
+pub use crate::interpreter::builder::{Configuration, ConfigurationBuilder, Platform};
+pub use crate::interpreter::{
+    audio::{AudioGenerator, AudioRecorder},
+    clock::{Clock, RealTimeClock, VirtualClock},
+    debugger::{
+        Comparison, Condition, ConditionError, Debugger, MemoryAccess, MemoryAccessKind, Operand,
+        StopReason, Watchpoint, STEP_FRAME_LIMIT,
+    },
+    display::{Display, Resolution},
+    error::ExecutionError,
+    movie::{Movie, MovieError, Replay, MOVIE_VERSION},
+    random::{RandomSource, RngRandomSource, ScriptedRandomSource, XorShiftRandomSource},
+    rewind::{RewindBudget, RewindBuffer},
+    save_state::{program_hash, SaveState, SaveStateError, SAVE_STATE_VERSION},
+    snapshot::{check_display_snapshot, BLESS_VARIABLE},
+    symbols::{SymbolError, SymbolTable},
 };
-use std::time::{Duration, Instant};
+use std::{ops::Range, time::Duration};
 
+mod audio;
 pub mod builder;
+mod clock;
+mod debugger;
+mod display;
+mod error;
 mod instructions;
+mod movie;
+mod random;
+mod rewind;
+mod save_state;
+mod snapshot;
+mod symbols;
+
+/// What happened during a successful call to [Interpreter::step].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The instruction was executed.
+    Executed(Instruction),
+
+    /// `Fx0A` is waiting for a key press. The `program_counter` did not move.
+    WaitingForKey,
+
+    /// `Dxyn` is waiting for the next frame. The `program_counter` did not move.
+    WaitingForFrame,
+
+    /// `00FD` ended the program. No more instructions will be executed.
+    Exited,
+}
 
 /// The chip8 Interpreter that manages the state of a program.
 #[derive(Debug)]
@@ -27,25 +70,65 @@ pub struct Interpreter {
     /// Keeps track of return memory locations when a subroutine is called
     call_stack: [u16; 16],
 
-    /// Keeps track of which entry in the stack should be returned to.
-    /// Determines current position in stack.
+    /// The number of entries in use in `call_stack`.
+    /// The most recent return address is at `call_stack_index - 1`.
     call_stack_index: usize,
 
+    /// The SUPER-CHIP "RPL user flags" saved and restored by `Fx75` and `Fx85`.
+    flag_registers: [u8; 16],
+
     /// Decrements at 60hz until zero
     delay_timer: u8,
 
     /// Decrements at 60hz until zero when a sound is played
     sound_timer: u8,
 
-    /// Using a simple PRNG in the Cxkk instruction.
-    /// This state is used to generate random numbers.
-    random_state: usize,
+    /// The key `Fx0A` saw pressed. The instruction completes once it is released.
+    key_awaiting_release: Option<u8>,
+
+    /// Set every time the timers tick (60hz) by [Interpreter::tick_timers].
+    /// Consumed by `Dxyn` when [Configuration::draw_waits_for_frame] is enabled.
+    vertical_blank: bool,
+
+    /// Generates the random numbers of the Cxkk instruction.
+    random_source: Box<dyn RandomSource>,
+
+    /// Color indexes with one bit per plane. `0` represents a black pixel.
+    display: Display,
+
+    /// XO-CHIP: the display planes affected by drawing, clearing and scrolling. One bit per plane.
+    selected_planes: u8,
+
+    /// 128 one bit samples played while the `sound_timer` is not zero. XO-CHIP programs can replace it.
+    audio_pattern: [u8; 16],
+
+    /// XO-CHIP: the playback rate of `audio_pattern` is `4000 * 2^((pitch - 64) / 48)` samples per second.
+    pitch: u8,
 
-    /// `false` represents a black pixel. `true` represents a white pixel
-    display: Box<[Box<[bool]>]>,
+    /// Switched by `00FE` and `00FF`.
+    resolution: Resolution,
 
-    last_timer_tick: Instant,
-    last_instruction_time: Instant,
+    /// Set when `resolution` changes. Cleared by [Interpreter::take_resolution_change].
+    resolution_changed: bool,
+
+    /// Set by `00FD`.
+    exited: bool,
+
+    /// The [program_hash] of the last loaded program.
+    program_hash: u64,
+
+    /// Ticks the timers and throttles [Interpreter::execute_current_instruction].
+    clock: Box<dyn Clock>,
+    /// The [Clock::now] of the last timer tick.
+    last_timer_tick: Duration,
+    /// The [Clock::now] of the last instruction executed by [Interpreter::execute_current_instruction].
+    last_instruction_time: Duration,
+
+    /// Names addresses in [Interpreter::describe_error] and [Interpreter::trace_line].
+    symbol_table: Option<SymbolTable>,
+
+    /// The memory read and written by the last [Interpreter::step]. Only recorded for a [Debugger].
+    memory_accesses: Option<Vec<MemoryAccess>>,
 
     /// A collection of four rows. `true` represents a pressed button. `false` represents a unpressed button
     /// ```text
@@ -84,20 +167,36 @@ impl Interpreter {
     pub const fn builder() -> ConfigurationBuilder {
         ConfigurationBuilder::new()
     }
+    /// A program that does not fit in memory is an [std::io::ErrorKind::InvalidData] error
+    /// wrapping [ExecutionError::ProgramTooLarge].
     pub fn load_program_from_path(
         &mut self,
         path: impl AsRef<std::path::Path>,
     ) -> Result<(), std::io::Error> {
         let program_data = std::fs::read(path)?;
-        self.load_program_from_bytes(program_data);
-        Ok(())
+        self.load_program_from_bytes(program_data)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
     }
-    pub fn load_program_from_bytes(&mut self, program_data: impl AsRef<[u8]>) {
+    /// Returns an error without changing memory if the program does not fit
+    /// between [Configuration::program_start] and the end of memory.
+    pub fn load_program_from_bytes(
+        &mut self,
+        program_data: impl AsRef<[u8]>,
+    ) -> Result<(), ExecutionError> {
         let program_data = program_data.as_ref();
         let program_size = program_data.len();
         let program_start = self.configuration.program_start();
 
+        let available = self.memory.len() - program_start;
+        if program_size > available {
+            return Err(ExecutionError::ProgramTooLarge {
+                program_size,
+                available,
+            });
+        }
         self.memory[program_start..program_start + program_size].copy_from_slice(program_data);
+        self.program_hash = program_hash(program_data);
+        Ok(())
     }
 }
 
@@ -107,26 +206,188 @@ impl Interpreter {
         &self.configuration
     }
 
-    pub fn display(&self) -> &[Box<[bool]>] {
+    pub const fn display(&self) -> &Display {
         &self.display
     }
 
-    /// Returns an array contain the four nibbles of an opcode.
-    /// (a nibble is a four bit number or single hexadecimal digit)
-    fn get_current_instruction(&self) -> Option<[u8; 4]> {
+    /// `V0` to `VF`.
+    pub const fn registers(&self) -> &[u8; 16] {
+        &self.variable_register
+    }
+
+    /// The address of the next instruction.
+    pub const fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// `I`
+    pub const fn address_register(&self) -> u16 {
+        self.address_register
+    }
+
+    /// The return addresses of the subroutines being executed. The most recent call is last.
+    pub fn call_stack(&self) -> &[u16] {
+        &self.call_stack[..self.call_stack_index]
+    }
+
+    /// The SUPER-CHIP "RPL user flags" saved and restored by `Fx75` and `Fx85`.
+    pub const fn flag_registers(&self) -> &[u8; 16] {
+        &self.flag_registers
+    }
+
+    pub const fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub const fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// All of memory, including the fonts below [Configuration::program_start].
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The current size of the [Interpreter::display].
+    pub const fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Returns `true` while a sound should play. See [AudioGenerator] for the samples.
+    pub const fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// The 128 one bit samples of the sound. The most significant bit of the first byte plays first.
+    pub const fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+
+    /// The bits of the [Interpreter::audio_pattern] played per second: `4000 * 2^((pitch - 64) / 48)`.
+    pub fn audio_playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// The keys pressed during the current frame. See [Interpreter::keypad_mut].
+    pub const fn keypad(&self) -> &[bool; 16] {
+        &self.keypad
+    }
+
+    /// The [program_hash] of the last loaded program.
+    pub const fn program_hash(&self) -> u64 {
+        self.program_hash
+    }
+
+    pub const fn symbol_table(&self) -> Option<&SymbolTable> {
+        self.symbol_table.as_ref()
+    }
+
+    /// Returns `address` as `label+0xOFFSET (file:line)` when a [SymbolTable] is set and as hexadecimal otherwise.
+    pub fn describe_address(&self, address: u16) -> String {
+        match &self.symbol_table {
+            Some(symbol_table) => symbol_table.describe(address),
+            None => format!("{address:#05X}"),
+        }
+    }
+
+    /// Returns `error` followed by the failed instruction and the calls that led to it.
+    /// Call this right after [Interpreter::step] failed, while the `program_counter` still points at the instruction.
+    /// ```text
+    /// unknown opcode 0xE0FF at address 0x206
+    ///     at draw+0x2 (game.8o:37)
+    ///     called from main_loop+0x4 (game.8o:12)
+    /// ```
+    pub fn describe_error(&self, error: &ExecutionError) -> String {
+        let mut description = format!(
+            "{error}\n    at {}",
+            self.describe_address(self.program_counter)
+        );
+        for &return_address in self.call_stack[..self.call_stack_index].iter().rev() {
+            let call_address = return_address.wrapping_sub(2);
+            description.push_str(&format!(
+                "\n    called from {}",
+                self.describe_address(call_address)
+            ));
+        }
+        description
+    }
+
+    /// Returns the instruction at the `program_counter` as a line of trace output.
+    /// ```text
+    /// main_loop+0x4 (game.8o:37)  6005  v0 := 0x05
+    /// ```
+    pub fn trace_line(&self) -> String {
+        let location = self.describe_address(self.program_counter);
+        let Some(opcode) = self.get_current_opcode() else {
+            return format!("{location}  out of bounds");
+        };
+        let Some(instruction) = self.decode(opcode) else {
+            return format!("{location}  {opcode:04X}  unknown opcode");
+        };
+
+        let long_address = self
+            .memory_range(self.program_counter as usize + 2, 2)
+            .map_or(0, |range| {
+                u16::from_be_bytes([self.memory[range.start], self.memory[range.start + 1]])
+            });
+        let address_name = |address| {
+            let label = self.symbol_table().and_then(|table| table.label(address));
+            label.map_or_else(|| format!("{address:#05X}"), str::to_owned)
+        };
+        let text = Syntax::Octo.format_instruction(instruction, long_address, address_name);
+        format!("{location}  {opcode:04X}  {text}")
+    }
+
+    /// Captures the state of the program. The [Clock] is not part of the state.
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            program_hash: self.program_hash,
+            configuration: self.configuration,
+            memory: self.memory.clone(),
+            program_counter: self.program_counter,
+            address_register: self.address_register,
+            variable_register: self.variable_register,
+            call_stack: self.call_stack,
+            call_stack_index: self.call_stack_index,
+            flag_registers: self.flag_registers,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            key_awaiting_release: self.key_awaiting_release,
+            vertical_blank: self.vertical_blank,
+            random_state: self.random_source.state(),
+            display: self.display.clone(),
+            selected_planes: self.selected_planes,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            resolution: self.resolution,
+            exited: self.exited,
+            keypad: self.keypad,
+        }
+    }
+
+    /// Returns the big-endian opcode at the `program_counter`.
+    fn get_current_opcode(&self) -> Option<u16> {
         let program_counter = self.program_counter as usize;
 
         let most_significant_byte = self.memory.get(program_counter)?;
         let least_significant_byte = self.memory.get(program_counter + 1)?;
 
-        let nibbles = [
-            get_first_nibble(*most_significant_byte),
-            get_second_nibble(*most_significant_byte),
-            get_first_nibble(*least_significant_byte),
-            get_second_nibble(*least_significant_byte),
-        ];
+        Some(u16::from_be_bytes([
+            *most_significant_byte,
+            *least_significant_byte,
+        ]))
+    }
 
-        Some(nibbles)
+    /// Returns `start..start + length` if that whole range is inside of memory.
+    /// Otherwise returns the first address that is out of bounds.
+    fn memory_range(&self, start: usize, length: usize) -> Result<Range<usize>, ExecutionError> {
+        match start.checked_add(length) {
+            Some(end) if end <= self.memory.len() => Ok(start..end),
+            _ => {
+                let address = start.max(self.memory.len());
+                Err(ExecutionError::MemoryOutOfBounds { address })
+            }
+        }
     }
 }
 
@@ -136,89 +397,636 @@ impl Interpreter {
         &mut self.keypad
     }
 
+    /// `V0` to `VF`. Every value is valid, so they can be changed freely.
+    pub const fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.variable_register
+    }
+
+    /// Returns an error without changing anything if `program_counter` does not point to a complete opcode in memory.
+    /// The last opcode of a 64 KiB memory is allowed, after it the program counter wraps around to 0.
+    pub fn set_program_counter(&mut self, program_counter: u16) -> Result<(), ExecutionError> {
+        self.memory_range(program_counter as usize, 2)
+            .map_err(|_| ExecutionError::ProgramCounterOutOfBounds { program_counter })?;
+        self.program_counter = program_counter;
+        Ok(())
+    }
+
+    /// Sets `I`. Instructions check that the memory they access through it exists.
+    pub fn set_address_register(&mut self, address: u16) {
+        self.address_register = address;
+    }
+
+    /// Replaces the return addresses of the subroutines being executed. The most recent call is last.
+    ///
+    /// Returns [ExecutionError::StackOverflow] without changing anything if there are more than 16.
+    pub fn set_call_stack(&mut self, return_addresses: &[u16]) -> Result<(), ExecutionError> {
+        let call_stack = self
+            .call_stack
+            .get_mut(..return_addresses.len())
+            .ok_or(ExecutionError::StackOverflow)?;
+        call_stack.copy_from_slice(return_addresses);
+        self.call_stack_index = return_addresses.len();
+        Ok(())
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// A non-zero value plays the sound. See [Interpreter::is_sound_active].
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Copies `bytes` into memory starting at `address`.
+    ///
+    /// Returns [ExecutionError::MemoryOutOfBounds] without changing anything if they do not fit.
+    /// The [Interpreter::program_hash] stays the one of the loaded program, so save states still load.
+    pub fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Result<(), ExecutionError> {
+        let range = self.memory_range(address, bytes.len())?;
+        self.memory[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Restores a state captured by [Interpreter::save_state], including its [Configuration].
+    ///
+    /// Returns an error if the state was saved while a different program was loaded.
+    /// A change of [Resolution] is reported by [Interpreter::take_resolution_change].
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), SaveStateError> {
+        if state.program_hash != self.program_hash {
+            return Err(SaveStateError::ProgramMismatch {
+                expected: self.program_hash,
+                found: state.program_hash,
+            });
+        }
+
+        self.resolution_changed |= state.resolution != self.resolution;
+        self.configuration = state.configuration;
+        self.memory.clone_from(&state.memory);
+        self.program_counter = state.program_counter;
+        self.address_register = state.address_register;
+        self.variable_register = state.variable_register;
+        self.call_stack = state.call_stack;
+        self.call_stack_index = state.call_stack_index;
+        self.flag_registers = state.flag_registers;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.key_awaiting_release = state.key_awaiting_release;
+        self.vertical_blank = state.vertical_blank;
+        self.random_source.set_state(state.random_state);
+        self.display.clone_from(&state.display);
+        self.selected_planes = state.selected_planes;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.resolution = state.resolution;
+        self.exited = state.exited;
+        self.keypad = state.keypad;
+        Ok(())
+    }
+
+    /// Replaces the [Clock] used by [Interpreter::execute_current_instruction].
+    ///
+    /// Use a [VirtualClock] for runs that must be reproducible.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.last_timer_tick = clock.now();
+        self.last_instruction_time = clock.now();
+        self.clock = Box::new(clock);
+    }
+
+    /// Replaces the [RandomSource] of `Cxkk`. The default is a [XorShiftRandomSource]
+    /// seeded with [Configuration::random_seed].
+    /// [ConfigurationBuilder::build_with_random_source] chooses it when building.
+    ///
+    /// Movies only replay identically with the default source.
+    pub fn set_random_source(&mut self, random_source: impl RandomSource + 'static) {
+        self.random_source = Box::new(random_source);
+    }
+
+    /// Names addresses in error descriptions and trace output. See [Interpreter::describe_address].
+    pub fn set_symbol_table(&mut self, symbol_table: Option<SymbolTable>) {
+        self.symbol_table = symbol_table;
+    }
+
+    /// Adds `range` to the memory accesses of the current instruction if they are being recorded.
+    fn record_memory_access(&mut self, kind: MemoryAccessKind, range: &Range<usize>) {
+        if let Some(memory_accesses) = &mut self.memory_accesses {
+            memory_accesses.push(MemoryAccess {
+                kind,
+                range: range.clone(),
+            });
+        }
+    }
+
+    /// Returns the new [Resolution] if the program switched resolution since the last call.
+    ///
+    /// Front-ends should call this after executing instructions and reallocate anything sized to the display.
+    pub fn take_resolution_change(&mut self) -> Option<Resolution> {
+        std::mem::take(&mut self.resolution_changed).then_some(self.resolution)
+    }
+
+    /// Decrements the timers once and starts a new frame (60hz).
+    ///
+    /// Called by [Interpreter::run_frame]. Hosts that use [Interpreter::run_cycles] call this themselves.
+    pub fn tick_timers(&mut self) {
+        self.vertical_blank = true;
+
+        // Decrement delay timer if > 0
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        // Decrement sound timer if > 0
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
     /// The timing and operation of the timers
     /// are completely separate from the fetch-decode-execute cycle.
     fn update_timers(&mut self) {
-        // We want to decrement our timers once every ~16.67ms (1/60s).
-        const TIMER_INTERVAL: Duration = Duration::from_nanos(16_666_667);
-        if self.last_timer_tick.elapsed() >= TIMER_INTERVAL {
-            self.last_timer_tick = Instant::now();
-
-            // Decrement delay timer if > 0
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
+        // We want to decrement our timers once every ~16.67ms (1/60s) of clock time.
+        while self.clock.now() - self.last_timer_tick >= TIMER_INTERVAL {
+            self.last_timer_tick += TIMER_INTERVAL;
+            self.tick_timers();
+        }
+    }
+
+    /// Executes one frame: up to [Configuration::instructions_per_frame] instructions followed by one tick of the timers.
+    ///
+    /// The frame ends early when the program exits or a `Dxyn` waits for the next frame.
+    /// Nothing sleeps, so the host decides how often frames run (normally 60 times per second).
+    pub fn run_frame(&mut self) -> Result<(), ExecutionError> {
+        self.run_cycles(self.configuration.instructions_per_frame())?;
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Executes up to `cycles` instructions without ticking the timers.
+    ///
+    /// Stops early when the program exits or a `Dxyn` waits for the next frame.
+    /// Returns the number of cycles used. A `Fx0A` waiting for a key uses a cycle.
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<usize, ExecutionError> {
+        for cycle in 0..cycles {
+            match self.step()? {
+                StepOutcome::Exited | StepOutcome::WaitingForFrame => return Ok(cycle),
+                StepOutcome::Executed(_) | StepOutcome::WaitingForKey => {}
             }
+        }
+        Ok(cycles)
+    }
+
+    /// Fetches, decodes and executes the instruction at the `program_counter`.
+    ///
+    /// The timers are not touched. See [Interpreter::run_frame] and [Interpreter::tick_timers].
+    ///
+    /// When an error is returned the `program_counter` is left pointing at the instruction that failed.
+    pub fn step(&mut self) -> Result<StepOutcome, ExecutionError> {
+        if self.exited {
+            return Ok(StepOutcome::Exited);
+        }
+
+        let program_counter = self.program_counter;
+        let opcode = self
+            .get_current_opcode()
+            .ok_or(ExecutionError::ProgramCounterOutOfBounds { program_counter })?;
+        let instruction = self.decode(opcode).ok_or(ExecutionError::UnknownOpcode {
+            program_counter,
+            opcode,
+        })?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        if let Some(memory_accesses) = &mut self.memory_accesses {
+            memory_accesses.clear();
+        }
+
+        self.execute(instruction).inspect_err(|_| {
+            self.program_counter = program_counter;
+        })
+    }
 
-            // Decrement sound timer if > 0, print "BEEP!!!"
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-                println!("BEEP!!!");
+    /// Decodes an opcode into an instruction supported by the configured [InstructionSet].
+    ///
+    /// On plain chip8 the SUPER-CHIP `00nn` opcodes stay `0nnn` machine language routines.
+    fn decode(&self, opcode: u16) -> Option<Instruction> {
+        match Instruction::decode(opcode) {
+            Ok(instruction)
+                if instruction.instruction_set() <= self.configuration.instruction_set() =>
+            {
+                Some(instruction)
             }
+            Ok(_) if opcode & 0xF000 == 0 => Some(Instruction::AssemblyRoutine {
+                nnn: opcode & 0x0FFF,
+            }),
+            _ => None,
         }
     }
 
-    #[rustfmt::skip]
+    /// Executes the current instruction in real time. Returns `false` if it could not be executed.
+    ///
+    /// The timers tick off the [Clock] and this sleeps for [Configuration::instruction_delay].
+    /// Prefer [Interpreter::run_frame] inside of a game loop.
+    /// See [Interpreter::step] for the reason of a failure.
     pub fn execute_current_instruction(&mut self) -> bool {
-
-        let Some(nibbles) = self.get_current_instruction() else {
+        if self.step().is_err() {
             return false;
-        };
-        self.program_counter += 2;
-
-        let address = concatenate_three_nibbles(nibbles[1], nibbles[2], nibbles[3]);
-        let value = concatenate_two_nibbles(nibbles[2], nibbles[3]);
-        let x_register_index = nibbles[1] as usize;
-        let y_register_index = nibbles[2] as usize;
-        let sprite_height = nibbles[3];
-
-        match nibbles {
-            [0x0, 0x0, 0xE, 0x0] => self.clear_display(),
-            [0x0, 0x0, 0xE, 0xE] => self.return_subroutine(),
-            [0x1,   _,   _,   _] => self.jump(address),
-            [0x2,   _,   _,   _] => self.call_subroutine(address),
-            [0x3,   _,   _,   _] => self.skip_if_equal_value(x_register_index, value),
-            [0x4,   _,   _,   _] => self.skip_if_not_equal_value(x_register_index, value),
-            [0x5,   _,   _, 0x0] => self.skip_if_equal(x_register_index, y_register_index),
-            [0x6,   _,   _,   _] => self.assign_value(x_register_index, value),
-            [0x7,   _,   _,   _] => self.add_assign_value(x_register_index, value),
-            [0x8,   _,   _, 0x0] => self.assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x1] => self.bitwise_or_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x2] => self.bitwise_and_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x3] => self.bitwise_xor_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x4] => self.add_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x5] => self.sub_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x6] => self.right_shift_assign(x_register_index, y_register_index),
-            [0x8,   _,   _, 0x7] => self.sub_assign_swapped(x_register_index, y_register_index),
-            [0x8,   _,   _, 0xE] => self.left_shift_assign(x_register_index, y_register_index),
-            [0x9,   _,   _, 0x0] => self.skip_if_not_equal(x_register_index, y_register_index),
-            [0xA,   _,   _,   _] => self.address_register_assign(address),
-            [0xB,   _,   _,   _] => self.jump_offset(x_register_index, address),
-            [0xC,   _,   _,   _] => self.random_number_assign(x_register_index, value),
-            [0xD,   _,   _,   _] => self.draw_sprite(x_register_index, y_register_index, sprite_height),
-            [0xE,   _, 0x9, 0xE] => self.skip_on_key_pressed(x_register_index),
-            [0xE,   _, 0xA, 0x1] => self.skip_on_key_not_pressed(x_register_index),
-            [0xF,   _, 0x0, 0x7] => self.store_delay_timer(x_register_index),
-            [0xF,   _, 0x0, 0xA] => self.wait_for_key_press(x_register_index),
-            [0xF,   _, 0x1, 0x5] => self.delay_timer_assign(x_register_index),
-            [0xF,   _, 0x1, 0x8] => self.sound_timer_assign(x_register_index),
-            [0xF,   _, 0x1, 0xE] => self.address_register_add_assign(x_register_index),
-            [0xF,   _, 0x2, 0x9] => self.address_register_assign_character_address(x_register_index),
-            [0xF,   _, 0x3, 0x3] => self.store_binary_coded_decimal_address(x_register_index),
-            [0xF,   _, 0x5, 0x5] => self.store_variable_registers(x_register_index),
-            [0xF,   _, 0x6, 0x5] => self.load_variable_registers(x_register_index),
-            _ => {}
         }
+        self.clock.cycle();
 
         self.update_timers();
 
-        let instruction_duration= self.last_instruction_time.elapsed();
-        self.last_instruction_time = Instant::now();
+        let next_instruction_time =
+            self.last_instruction_time + self.configuration.instruction_delay();
+        self.clock.sleep_until(next_instruction_time);
+        self.last_instruction_time = self.clock.now();
+
+        true
+    }
 
-        let instruction_delay = self.configuration.instruction_delay();
-        if instruction_duration < instruction_delay {
-            std::thread::sleep(instruction_delay - instruction_duration);
+    #[rustfmt::skip]
+    fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, ExecutionError> {
+        match instruction {
+            Instruction::AssemblyRoutine { nnn } => self.assembly_routine(nnn)?,
+            Instruction::ClearDisplay => self.clear_display(),
+            Instruction::ReturnSubroutine => self.return_subroutine()?,
+            Instruction::ScrollDown { n } => self.scroll_down(n as usize),
+            Instruction::ScrollUp { n } => self.scroll_up(n as usize),
+            Instruction::ScrollRight => self.scroll_right(),
+            Instruction::ScrollLeft => self.scroll_left(),
+            Instruction::Exit => {
+                self.exit();
+                return Ok(StepOutcome::Exited);
+            }
+            Instruction::LowResolution => self.low_resolution(),
+            Instruction::HighResolution => self.high_resolution(),
+            Instruction::Jump { nnn } => self.jump(nnn),
+            Instruction::CallSubroutine { nnn } => self.call_subroutine(nnn)?,
+            Instruction::SkipIfEqualValue { x, kk } => self.skip_if_equal_value(x as usize, kk),
+            Instruction::SkipIfNotEqualValue { x, kk } => self.skip_if_not_equal_value(x as usize, kk),
+            Instruction::SkipIfEqual { x, y } => self.skip_if_equal(x as usize, y as usize),
+            Instruction::StoreRegisterRange { x, y } => self.store_register_range(x as usize, y as usize)?,
+            Instruction::LoadRegisterRange { x, y } => self.load_register_range(x as usize, y as usize)?,
+            Instruction::AssignValue { x, kk } => self.assign_value(x as usize, kk),
+            Instruction::AddAssignValue { x, kk } => self.add_assign_value(x as usize, kk),
+            Instruction::Assign { x, y } => self.assign(x as usize, y as usize),
+            Instruction::BitwiseOrAssign { x, y } => self.bitwise_or_assign(x as usize, y as usize),
+            Instruction::BitwiseAndAssign { x, y } => self.bitwise_and_assign(x as usize, y as usize),
+            Instruction::BitwiseXorAssign { x, y } => self.bitwise_xor_assign(x as usize, y as usize),
+            Instruction::AddAssign { x, y } => self.add_assign(x as usize, y as usize),
+            Instruction::SubAssign { x, y } => self.sub_assign(x as usize, y as usize),
+            Instruction::RightShiftAssign { x, y } => self.right_shift_assign(x as usize, y as usize),
+            Instruction::SubAssignSwapped { x, y } => self.sub_assign_swapped(x as usize, y as usize),
+            Instruction::LeftShiftAssign { x, y } => self.left_shift_assign(x as usize, y as usize),
+            Instruction::SkipIfNotEqual { x, y } => self.skip_if_not_equal(x as usize, y as usize),
+            Instruction::AddressRegisterAssign { nnn } => self.address_register_assign(nnn),
+            Instruction::JumpOffset { x, nnn } => self.jump_offset(x as usize, nnn),
+            Instruction::RandomNumberAssign { x, kk } => self.random_number_assign(x as usize, kk),
+            Instruction::DrawSprite { x, y, n } => {
+                if !self.draw_sprite(x as usize, y as usize, n)? {
+                    return Ok(StepOutcome::WaitingForFrame);
+                }
+            }
+            Instruction::SkipOnKeyPressed { x } => self.skip_on_key_pressed(x as usize),
+            Instruction::SkipOnKeyNotPressed { x } => self.skip_on_key_not_pressed(x as usize),
+            Instruction::AddressRegisterAssignLong => self.address_register_assign_long()?,
+            Instruction::SelectPlanes { n } => self.select_planes(n),
+            Instruction::StoreAudioPattern => self.store_audio_pattern()?,
+            Instruction::StoreDelayTimer { x } => self.store_delay_timer(x as usize),
+            Instruction::WaitForKeyPress { x } => {
+                if !self.wait_for_key_press(x as usize) {
+                    return Ok(StepOutcome::WaitingForKey);
+                }
+            }
+            Instruction::DelayTimerAssign { x } => self.delay_timer_assign(x as usize),
+            Instruction::SoundTimerAssign { x } => self.sound_timer_assign(x as usize),
+            Instruction::AddressRegisterAddAssign { x } => self.address_register_add_assign(x as usize),
+            Instruction::AddressRegisterAssignCharacterAddress { x } => self.address_register_assign_character_address(x as usize),
+            Instruction::AddressRegisterAssignBigCharacterAddress { x } => self.address_register_assign_big_character_address(x as usize),
+            Instruction::StoreBinaryCodedDecimalAddress { x } => self.store_binary_coded_decimal_address(x as usize)?,
+            Instruction::PitchAssign { x } => self.pitch_assign(x as usize),
+            Instruction::StoreVariableRegisters { x } => self.store_variable_registers(x as usize)?,
+            Instruction::LoadVariableRegisters { x } => self.load_variable_registers(x as usize)?,
+            Instruction::StoreFlagRegisters { x } => self.store_flag_registers(x as usize),
+            Instruction::LoadFlagRegisters { x } => self.load_flag_registers(x as usize),
         }
 
-        true
+        Ok(StepOutcome::Executed(instruction))
+    }
+}
+
+#[test]
+fn test_step_unknown_opcode() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load_program_from_bytes([0xFF, 0xFF]).unwrap();
+    let error = interpreter.step().unwrap_err();
+    let expected = ExecutionError::UnknownOpcode {
+        program_counter: 0x200,
+        opcode: 0xFFFF,
+    };
+    assert_eq!(error, expected);
+    assert_eq!(interpreter.program_counter, 0x200);
+}
+#[test]
+fn test_step_stack_underflow() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load_program_from_bytes([0x00, 0xEE]).unwrap();
+    assert_eq!(interpreter.step(), Err(ExecutionError::StackUnderflow));
+}
+#[test]
+fn test_step_stack_overflow() {
+    let mut interpreter = Interpreter::default();
+    // 0x200: call 0x200
+    interpreter.load_program_from_bytes([0x22, 0x00]).unwrap();
+    for _ in 0..interpreter.call_stack.len() {
+        interpreter.step().unwrap();
     }
+    assert_eq!(interpreter.step(), Err(ExecutionError::StackOverflow));
+}
+#[test]
+fn test_step_memory_out_of_bounds() {
+    let mut interpreter = Interpreter::default();
+    // I = 0xFFF, store V0..=V1 at I
+    interpreter
+        .load_program_from_bytes([0xAF, 0xFF, 0xF1, 0x55])
+        .unwrap();
+    interpreter.step().unwrap();
+    let expected = ExecutionError::MemoryOutOfBounds { address: 0x1000 };
+    assert_eq!(interpreter.step(), Err(expected));
+}
+#[test]
+fn test_step_program_counter_out_of_bounds() {
+    let mut interpreter = Interpreter::default();
+    // jump 0xFFF
+    interpreter.load_program_from_bytes([0x1F, 0xFF]).unwrap();
+    interpreter.step().unwrap();
+    let expected = ExecutionError::ProgramCounterOutOfBounds {
+        program_counter: 0xFFF,
+    };
+    assert_eq!(interpreter.step(), Err(expected));
+}
+#[test]
+fn test_load_program_too_large() {
+    use crate::interpreter::builder::{DEFAULT_MEMORY_SIZE, DEFAULT_PROGRAM_START};
+    let mut interpreter = Interpreter::default();
+    let available = DEFAULT_MEMORY_SIZE - DEFAULT_PROGRAM_START;
+    let program = vec![0xFF; available + 1];
+    assert_eq!(
+        interpreter.load_program_from_bytes(&program),
+        Err(ExecutionError::ProgramTooLarge {
+            program_size: available + 1,
+            available
+        })
+    );
+    assert!(interpreter.memory()[DEFAULT_PROGRAM_START..]
+        .iter()
+        .all(|&byte| byte == 0));
+    interpreter.load_program_from_bytes(&program[1..]).unwrap();
+}
+#[test]
+fn test_step_wraps_at_top_of_memory() {
+    let mut interpreter = ConfigurationBuilder::preset(Platform::XoChip).build();
+    // 0xFFFA: skip if V0 == 0, 0xFFFE: V1 = 5
+    interpreter
+        .write_memory(0xFFFA, &[0x30, 0x00, 0x00, 0x00, 0x61, 0x05])
+        .unwrap();
+    interpreter.set_program_counter(0xFFFA).unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.program_counter(), 0xFFFE);
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.registers()[1], 5);
+    assert_eq!(interpreter.program_counter(), 0);
+}
+#[test]
+fn test_wait_at_top_of_memory() {
+    // 0xFFFA: skip if V0 == 0, 0xFFFE: V1 = key or draw 1 row at (V0, V0)
+    for opcode in [[0xF1, 0x0A], [0xD0, 0x01]] {
+        let mut interpreter = ConfigurationBuilder::preset(Platform::XoChip)
+            .draw_waits_for_frame(true)
+            .build();
+        interpreter
+            .write_memory(0xFFFA, &[0x30, 0x00, 0x00, 0x00, opcode[0], opcode[1]])
+            .unwrap();
+        interpreter.set_program_counter(0xFFFA).unwrap();
+        interpreter.step().unwrap();
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.program_counter(), 0xFFFE);
+    }
+}
+#[test]
+fn test_run_frame_ticks_timers_once() {
+    let mut interpreter = Interpreter::builder().instructions_per_frame(4).build();
+    // V0 = 10, delay timer = V0, 0x204: jump 0x204
+    interpreter
+        .load_program_from_bytes([0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04])
+        .unwrap();
+    interpreter.run_frame().unwrap();
+    assert_eq!(interpreter.delay_timer, 9);
+    interpreter.run_frame().unwrap();
+    assert_eq!(interpreter.delay_timer, 8);
+}
+#[test]
+fn test_run_cycles_budget() {
+    let mut interpreter = Interpreter::default();
+    // V0 += 1, jump 0x200
+    interpreter
+        .load_program_from_bytes([0x70, 0x01, 0x12, 0x00])
+        .unwrap();
+    assert_eq!(interpreter.run_cycles(10), Ok(10));
+    assert_eq!(interpreter.variable_register[0], 5);
+}
+#[test]
+fn test_run_frame_ends_when_draw_waits() {
+    let mut interpreter = Interpreter::builder()
+        .draw_waits_for_frame(true)
+        .instructions_per_frame(100)
+        .build();
+    // draw, jump 0x200
+    interpreter
+        .load_program_from_bytes([0xD0, 0x01, 0x12, 0x00])
+        .unwrap();
+    interpreter.tick_timers();
+    // the first draw uses the vertical blank and the second draw waits for the next frame
+    assert_eq!(interpreter.run_cycles(100), Ok(2));
+    assert_eq!(interpreter.program_counter, 0x200);
+}
+#[test]
+fn test_virtual_clock_ticks_timers() {
+    let mut interpreter = Interpreter::default();
+    interpreter.set_clock(VirtualClock::new(4));
+    // V0 = 10, delay timer = V0, 0x204: jump 0x204
+    interpreter
+        .load_program_from_bytes([0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04])
+        .unwrap();
+    for _ in 0..3 {
+        assert!(interpreter.execute_current_instruction());
+    }
+    assert_eq!(interpreter.delay_timer, 10);
+    // the fourth instruction ends the first frame
+    assert!(interpreter.execute_current_instruction());
+    assert_eq!(interpreter.delay_timer, 9);
+    for _ in 0..4 {
+        assert!(interpreter.execute_current_instruction());
+    }
+    assert_eq!(interpreter.delay_timer, 8);
+}
+#[test]
+fn test_save_state_round_trip() {
+    let mut interpreter = ConfigurationBuilder::preset(Platform::SuperChip11).build();
+    // V0 = 5, high resolution, draw, call 0x20A, 0x20A: V0 += 1
+    let program = [
+        0x60, 0x05, 0x00, 0xFF, 0xD0, 0x05, 0x22, 0x0A, 0x00, 0x00, 0x70, 0x01,
+    ];
+    interpreter.load_program_from_bytes(program).unwrap();
+    interpreter.run_cycles(5).unwrap();
+    let state = interpreter.save_state();
+
+    let decoded = SaveState::from_bytes(&state.to_bytes()).unwrap();
+    assert_eq!(decoded, state);
+
+    let mut restored = ConfigurationBuilder::preset(Platform::SuperChip11).build();
+    restored.load_program_from_bytes(program).unwrap();
+    restored.load_state(&decoded).unwrap();
+    assert_eq!(restored.take_resolution_change(), Some(Resolution::High));
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(restored.variable_register[0], 6);
+    assert_eq!(restored.call_stack_index, 1);
+}
+#[test]
+fn test_save_state_program_mismatch() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load_program_from_bytes([0x12, 0x00]).unwrap();
+    let state = interpreter.save_state();
+    interpreter.load_program_from_bytes([0x12, 0x02]).unwrap();
+    assert!(matches!(
+        interpreter.load_state(&state),
+        Err(SaveStateError::ProgramMismatch { .. })
+    ));
+}
+#[test]
+fn test_save_state_from_bytes_errors() {
+    let bytes = Interpreter::default().save_state().to_bytes();
+    let truncated = SaveState::from_bytes(&bytes[..bytes.len() - 1]);
+    assert_eq!(truncated, Err(SaveStateError::UnexpectedEnd));
+    let unknown = SaveState::from_bytes(b"nope");
+    assert_eq!(unknown, Err(SaveStateError::UnknownFormat));
+
+    let mut future_version = bytes.clone();
+    future_version[4..6].copy_from_slice(&(SAVE_STATE_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        SaveState::from_bytes(&future_version),
+        Err(SaveStateError::UnsupportedVersion(SAVE_STATE_VERSION + 1))
+    );
+
+    let state = Interpreter::default().save_state();
+    let invalid_states = [
+        SaveState {
+            key_awaiting_release: Some(200),
+            ..state.clone()
+        },
+        SaveState {
+            display: Display::new(0, 32, 1),
+            ..state.clone()
+        },
+        SaveState {
+            resolution: Resolution::High,
+            ..state.clone()
+        },
+        SaveState {
+            selected_planes: 0b10,
+            ..state
+        },
+    ];
+    for invalid_state in invalid_states {
+        let decoded = SaveState::from_bytes(&invalid_state.to_bytes());
+        assert_eq!(decoded, Err(SaveStateError::InvalidValue));
+    }
+}
+#[test]
+fn test_scripted_random_source() {
+    let mut interpreter =
+        Interpreter::builder().build_with_random_source(ScriptedRandomSource::new([0xAB, 0xFF]));
+    // V0 = random & 0x0F, V1 = random & 0xF0
+    interpreter
+        .load_program_from_bytes([0xC0, 0x0F, 0xC1, 0xF0])
+        .unwrap();
+    interpreter.run_cycles(2).unwrap();
+    assert_eq!(interpreter.variable_register[..2], [0x0B, 0xF0]);
+}
+#[test]
+fn test_describe_error_with_symbols() {
+    let source = "\
+: main
+    v0 := 1
+    draw
+: draw
+    v1 := 2
+    0xE0 0xFF
+";
+    let assembly = crate::asm::Assembly::new(source).unwrap();
+    let mut interpreter = Interpreter::default();
+    interpreter
+        .load_program_from_bytes(assembly.bytes())
+        .unwrap();
+    interpreter.set_symbol_table(Some(assembly.symbol_table("game.8o")));
+
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.trace_line(), "main+0x2 (game.8o:3)  2204  draw");
+    let error = interpreter.run_cycles(3).unwrap_err();
+    let expected = "\
+unknown opcode 0xE0FF at address 0x206
+    at draw+0x2 (game.8o:6)
+    called from main+0x2 (game.8o:3)";
+    assert_eq!(interpreter.describe_error(&error), expected);
+}
+#[test]
+fn test_inspect_and_modify_state() {
+    let mut interpreter = Interpreter::default();
+    // 0x200: call 0x204, 0x204: I := 0x300, v3 := 0x10, save v3
+    interpreter
+        .load_program_from_bytes([0x22, 0x04, 0x00, 0x00, 0xA3, 0x00, 0x63, 0x10, 0xF3, 0x55])
+        .unwrap();
+    interpreter.run_cycles(4).unwrap();
+    assert_eq!(interpreter.program_counter(), 0x20A);
+    assert_eq!(interpreter.address_register(), 0x300);
+    assert_eq!(interpreter.registers()[3], 0x10);
+    assert_eq!(interpreter.call_stack(), [0x202]);
+    assert_eq!(interpreter.memory()[0x300..0x304], [0, 0, 0, 0x10]);
+
+    let memory_size = interpreter.memory().len();
+    assert_eq!(
+        interpreter.write_memory(memory_size - 1, &[1, 2]),
+        Err(ExecutionError::MemoryOutOfBounds {
+            address: memory_size
+        })
+    );
+    interpreter.write_memory(0x300, &[7]).unwrap();
+    assert_eq!(interpreter.memory()[0x300], 7);
+
+    assert_eq!(
+        interpreter.set_program_counter(memory_size as u16 - 1),
+        Err(ExecutionError::ProgramCounterOutOfBounds {
+            program_counter: memory_size as u16 - 1
+        })
+    );
+    assert_eq!(interpreter.program_counter(), 0x20A);
+    assert_eq!(
+        interpreter.set_call_stack(&[0x200; 17]),
+        Err(ExecutionError::StackOverflow)
+    );
+    interpreter.set_call_stack(&[]).unwrap();
+    assert!(interpreter.call_stack().is_empty());
+
+    assert_eq!(
+        interpreter.write_memory(usize::MAX, &[0]),
+        Err(ExecutionError::MemoryOutOfBounds {
+            address: usize::MAX
+        })
+    );
+    let mut interpreter = ConfigurationBuilder::preset(Platform::XoChip).build();
+    interpreter.set_program_counter(0xFFFE).unwrap();
+    assert_eq!(interpreter.program_counter(), 0xFFFE);
+    assert_eq!(
+        interpreter.set_program_counter(0xFFFF),
+        Err(ExecutionError::ProgramCounterOutOfBounds {
+            program_counter: 0xFFFF
+        })
+    );
 }
@@ -0,0 +1,223 @@
+use crate::interpreter::Interpreter;
+
+/// Turns the sound of an [Interpreter] into PCM samples.
+///
+/// While [Interpreter::is_sound_active] the 128 bit audio pattern is played at the
+/// [Interpreter::audio_playback_rate]. The default pattern is a 250hz square wave.
+/// XO-CHIP programs can replace the pattern with `F002` and change the pitch with `Fx3A`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioGenerator {
+    sample_rate: u32,
+
+    /// The amplitude of a set bit. Unset bits are `-volume`.
+    volume: f32,
+
+    /// The position in the audio pattern in bits. Kept between calls so the wave has no seams.
+    position: f64,
+}
+
+// initialization
+impl AudioGenerator {
+    pub const DEFAULT_VOLUME: f32 = 0.25;
+
+    /// # Panics
+    /// If `sample_rate` is zero
+    pub fn new(sample_rate: u32) -> Self {
+        assert!(sample_rate > 0, "the sample rate must not be zero");
+        Self {
+            sample_rate,
+            volume: Self::DEFAULT_VOLUME,
+            position: 0.0,
+        }
+    }
+}
+
+// accessors
+impl AudioGenerator {
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub const fn volume(&self) -> f32 {
+        self.volume
+    }
+}
+
+// mutators
+impl AudioGenerator {
+    /// Sets the amplitude of the wave from `0.0` to `1.0`.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Fills `samples` with mono samples from `-1.0` to `1.0`. Silence is `0.0`.
+    ///
+    /// Call this with about one frame of samples (`sample_rate / 60`) after every [Interpreter::run_frame].
+    pub fn fill(&mut self, interpreter: &Interpreter, samples: &mut [f32]) {
+        if !interpreter.is_sound_active() {
+            self.position = 0.0;
+            samples.fill(0.0);
+            return;
+        }
+
+        let audio_pattern = interpreter.audio_pattern();
+        let pattern_length = (audio_pattern.len() * 8) as f64;
+        let bits_per_sample = interpreter.audio_playback_rate() / self.sample_rate as f64;
+        for sample in samples {
+            let bit = self.position as usize;
+            let is_set = audio_pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if is_set { self.volume } else { -self.volume };
+            self.position = (self.position + bits_per_sample) % pattern_length;
+        }
+    }
+
+    /// Returns `sample_count` samples. See [AudioGenerator::fill].
+    pub fn generate(&mut self, interpreter: &Interpreter, sample_count: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; sample_count];
+        self.fill(interpreter, &mut samples);
+        samples
+    }
+}
+
+/// Records the sound of a run one frame at a time and encodes it as a `.wav` file.
+///
+/// Runs driven by [Interpreter::run_frame] are deterministic, so recordings can be compared in tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioRecorder {
+    generator: AudioGenerator,
+
+    /// 16 bit mono samples.
+    samples: Vec<i16>,
+
+    /// The number of recorded frames.
+    frame_count: u64,
+}
+
+// initialization
+impl AudioRecorder {
+    /// # Panics
+    /// If `sample_rate` is zero
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            generator: AudioGenerator::new(sample_rate),
+            samples: Vec::new(),
+            frame_count: 0,
+        }
+    }
+}
+
+// accessors
+impl AudioRecorder {
+    pub const fn sample_rate(&self) -> u32 {
+        self.generator.sample_rate()
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// The length of the recording in seconds.
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate() as f64
+    }
+
+    /// Encodes the recording as a 16 bit mono PCM `.wav` file.
+    pub fn to_wav_bytes(&self) -> Vec<u8> {
+        const HEADER_SIZE: u32 = 44;
+        const CHANNEL_COUNT: u16 = 1;
+        const BYTES_PER_SAMPLE: u16 = 2;
+
+        let data_size = (self.samples.len() * BYTES_PER_SAMPLE as usize) as u32;
+        let mut wav = Vec::with_capacity((HEADER_SIZE + data_size) as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&CHANNEL_COUNT.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate().to_le_bytes());
+        let block_size = CHANNEL_COUNT * BYTES_PER_SAMPLE;
+        wav.extend_from_slice(&(self.sample_rate() * block_size as u32).to_le_bytes());
+        wav.extend_from_slice(&block_size.to_le_bytes());
+        wav.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+// mutators
+impl AudioRecorder {
+    /// Records one frame (1/60s) of sound. Call this after every [Interpreter::run_frame].
+    pub fn record_frame(&mut self, interpreter: &Interpreter) {
+        // frames do not always hold a whole number of samples
+        let sample_rate = self.sample_rate() as u64;
+        let start = self.frame_count * sample_rate / 60;
+        let end = (self.frame_count + 1) * sample_rate / 60;
+        self.frame_count += 1;
+
+        let samples = self.generator.generate(interpreter, (end - start) as usize);
+        self.samples.extend(
+            samples
+                .into_iter()
+                .map(|sample| (sample * i16::MAX as f32) as i16),
+        );
+    }
+
+    /// Writes the recording to a `.wav` file.
+    pub fn write_wav(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
+        std::fs::write(path, self.to_wav_bytes())
+    }
+}
+
+#[test]
+fn test_default_square_wave() {
+    let mut interpreter = Interpreter::default();
+    let mut audio_generator = AudioGenerator::new(8000);
+    assert!(audio_generator
+        .generate(&interpreter, 4)
+        .iter()
+        .all(|&sample| sample == 0.0));
+
+    // V0 = 2, sound timer = V0
+    interpreter
+        .load_program_from_bytes([0x60, 0x02, 0xF0, 0x18])
+        .unwrap();
+    interpreter.run_cycles(2).unwrap();
+    // 4000 bits per second at 8000 samples per second: 16 samples for each byte of the pattern
+    let samples = audio_generator.generate(&interpreter, 64);
+    let volume = AudioGenerator::DEFAULT_VOLUME;
+    assert!(samples[..16].iter().all(|&sample| sample == volume));
+    assert!(samples[16..32].iter().all(|&sample| sample == -volume));
+    assert!(samples[32..48].iter().all(|&sample| sample == volume));
+}
+#[test]
+fn test_audio_recorder_frames() {
+    let mut interpreter = Interpreter::builder().instructions_per_frame(2).build();
+    // V0 = 2, sound timer = V0, 0x204: jump 0x204
+    interpreter
+        .load_program_from_bytes([0x60, 0x02, 0xF0, 0x18, 0x12, 0x04])
+        .unwrap();
+    let mut audio_recorder = AudioRecorder::new(600);
+    for _ in 0..4 {
+        interpreter.run_frame().unwrap();
+        audio_recorder.record_frame(&interpreter);
+    }
+
+    // the sound timer is 1 after the first frame and 0 after the second
+    let samples = audio_recorder.samples();
+    assert_eq!(samples.len(), 40);
+    assert!(samples[..10].iter().all(|&sample| sample != 0));
+    assert!(samples[10..].iter().all(|&sample| sample == 0));
+
+    let wav = audio_recorder.to_wav_bytes();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(wav.len(), 44 + 40 * 2);
+}
@@ -1,5 +1,7 @@
-use super::Interpreter;
-use std::time::{Duration, Instant};
+use super::save_state::{program_hash, Reader, SaveStateError, Writer};
+use super::{Display, Interpreter, RandomSource, RealTimeClock, Resolution, XorShiftRandomSource};
+use crate::instruction::InstructionSet;
+use std::time::Duration;
 
 /// Offset is commonly done because of old standards.
 /// Most programs written for Chip8 expect programs to start here.
@@ -17,54 +19,355 @@ pub const DEFAULT_FONT_DATA: [u8; 80] = [
 ];
 pub const DEFAULT_FONT_DATA_START: usize = 0x50;
 pub const DEFAULT_FONT_DATA_END: usize = 0x9F;
+/// 8x10 pixel hexadecimal digits used by `Fx30`.
+pub const DEFAULT_BIG_FONT_DATA: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x18, 0x78, 0x78, 0x18, 0x18, 0x18,
+    0x18, 0x18, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03,
+    0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xFC, 0xFC,
+    0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3,
+    0xFF, 0x3C, 0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,
+];
+pub const DEFAULT_BIG_FONT_DATA_START: usize = 0xA0;
+pub const DEFAULT_BIG_FONT_DATA_END: usize = 0x13F;
 pub const DEFAULT_INSTRUCTION_DELAY: Duration = Duration::from_nanos(((1.0 / 700.0) * 1e9) as u64);
+/// About 700 instructions per second.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 12;
+/// The time between timer ticks (60hz).
+pub const TIMER_INTERVAL: Duration = Duration::from_nanos(16_666_667);
 pub const DEFAULT_MEMORY_SIZE: usize = 4096;
+pub const HIGH_RESOLUTION_DISPLAY_WIDTH: usize = 128;
+pub const HIGH_RESOLUTION_DISPLAY_HEIGHT: usize = 64;
+pub const XO_CHIP_MEMORY_SIZE: usize = 65536;
+pub const XO_CHIP_DISPLAY_PLANE_COUNT: usize = 2;
+pub const DEFAULT_PITCH: u8 = 64;
+/// A 250hz square wave at the [DEFAULT_PITCH].
+pub const DEFAULT_AUDIO_PATTERN: [u8; 16] = [
+    0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00,
+];
+pub const DEFAULT_RANDOM_SEED: u64 = 0x13275389;
+
+/// Returns the delay between instructions needed to execute `instructions_per_frame` instructions every frame (60hz).
+pub const fn instruction_delay_from_speed(instructions_per_frame: u64) -> Duration {
+    Duration::from_nanos(1_000_000_000 / (60 * instructions_per_frame))
+}
+
+/// The chip8 implementations that [Configuration::preset] can imitate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// The original chip8 interpreter on the RCA COSMAC VIP (1977).
+    CosmacVip,
+    /// CHIP-48 on the HP-48 calculators (1990).
+    Chip48,
+    /// SUPER-CHIP 1.0 on the HP-48 calculators (1991).
+    SuperChip10,
+    /// SUPER-CHIP 1.1 on the HP-48 calculators (1991).
+    SuperChip11,
+    /// XO-CHIP as defined by Octo (2014).
+    XoChip,
+}
+impl Platform {
+    pub const ALL: [Self; 5] = [
+        Self::CosmacVip,
+        Self::Chip48,
+        Self::SuperChip10,
+        Self::SuperChip11,
+        Self::XoChip,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::CosmacVip => "COSMAC VIP",
+            Self::Chip48 => "CHIP-48",
+            Self::SuperChip10 => "SUPER-CHIP 1.0",
+            Self::SuperChip11 => "SUPER-CHIP 1.1",
+            Self::XoChip => "XO-CHIP",
+        }
+    }
+}
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
 
-#[derive(Debug, Clone, Copy, macros::CopyGetters, macros::Builder)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, macros::CopyGetters, macros::Builder)]
 pub struct Configuration {
+    /// The minimum time between instructions executed by [Interpreter::execute_current_instruction].
     instruction_delay: Duration,
+    /// The number of instructions executed by [Interpreter::run_frame].
+    instructions_per_frame: usize,
     memory_size: usize,
+
+    /// `Fx0A`: the sound plays while the pressed key is held down.
     key_held_plays_sound: bool,
+
+    /// `Fx0A`: the key is stored once it is released. When `false` it is stored as soon as it is pressed.
+    wait_for_key_release: bool,
+
+    /// `0nnn`: when `true` a machine language routine is called, which is reported as an error
+    /// because it can not be emulated. When `false` the instruction is ignored.
     use_assembly_routine: bool,
+
+    /// `Bnnn`: when `true` jumps to `nnn` plus `VX` (where X is the first nibble of `nnn`).
+    /// When `false` jumps to `nnn` plus `V0`.
     use_variable_offset: bool,
+
+    /// `Fx55` and `Fx65`: `I` is incremented by one for each register stored or loaded.
     increment_on_store: bool,
+
+    /// `8xy6` and `8xyE`: when `true` `VY` is shifted into `VX`. When `false` `VX` is shifted in place.
+    shift_uses_y_register: bool,
+
+    /// `8xy1`, `8xy2` and `8xy3`: `VF` is reset to 0.
+    logic_resets_flag: bool,
+
+    /// `Dxyn`: sprites are clipped at the edges of the display instead of wrapping around.
+    clip_sprites: bool,
+
+    /// `Dxyn`: waits for the start of the next frame (60hz) before drawing. Limits drawing to one sprite per frame.
+    draw_waits_for_frame: bool,
+
+    /// The newest chip8 extension whose instructions can be executed.
+    /// Instructions from newer extensions are reported as unknown opcodes.
+    instruction_set: InstructionSet,
+
     program_start: usize,
-    display_width: usize,
-    display_height: usize,
+    /// The size of the display before the program switches resolution.
+    initial_resolution: Resolution,
+
+    /// The number of XO-CHIP display planes. From 1 to [Display::MAX_PLANE_COUNT].
+    display_plane_count: usize,
+
+    /// Seeds the default [RandomSource](super::RandomSource) of `Cxkk`. A seed of zero only generates zeros.
+    /// Defaults to [DEFAULT_RANDOM_SEED], so every run generates the same numbers unless
+    /// [ConfigurationBuilder::random_seed_from_entropy] is used.
+    random_seed: u64,
+
     font_data: [u8; 80],
     font_data_start: usize,
     font_data_end: usize,
+    big_font_data: [u8; 160],
+    big_font_data_start: usize,
+    big_font_data_end: usize,
 }
 impl Configuration {
     pub const fn new() -> Self {
         Self {
             instruction_delay: DEFAULT_INSTRUCTION_DELAY,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
             key_held_plays_sound: true,
+            wait_for_key_release: false,
             use_assembly_routine: false,
             use_variable_offset: true,
             increment_on_store: false,
+            shift_uses_y_register: false,
+            logic_resets_flag: false,
+            clip_sprites: false,
+            draw_waits_for_frame: false,
+            instruction_set: InstructionSet::Chip8,
             program_start: DEFAULT_PROGRAM_START,
-            display_width: DEFAULT_DISPLAY_WIDTH,
-            display_height: DEFAULT_DISPLAY_HEIGHT,
+            initial_resolution: Resolution::Low,
+            display_plane_count: 1,
+            random_seed: DEFAULT_RANDOM_SEED,
             font_data: DEFAULT_FONT_DATA,
             font_data_start: DEFAULT_FONT_DATA_START,
             font_data_end: DEFAULT_FONT_DATA_END,
+            big_font_data: DEFAULT_BIG_FONT_DATA,
+            big_font_data_start: DEFAULT_BIG_FONT_DATA_START,
+            big_font_data_end: DEFAULT_BIG_FONT_DATA_END,
             memory_size: DEFAULT_MEMORY_SIZE,
         }
     }
 }
+impl Configuration {
+    /// Returns the quirks, display planes, memory size, program start and speed of the given [Platform].
+    pub const fn preset(platform: Platform) -> Self {
+        let configuration = Self::new();
+        match platform {
+            Platform::CosmacVip => Self {
+                instruction_delay: instruction_delay_from_speed(15),
+                instructions_per_frame: 15,
+                key_held_plays_sound: true,
+                wait_for_key_release: true,
+                use_assembly_routine: true,
+                use_variable_offset: false,
+                increment_on_store: true,
+                shift_uses_y_register: true,
+                logic_resets_flag: true,
+                clip_sprites: true,
+                draw_waits_for_frame: true,
+                ..configuration
+            },
+            Platform::Chip48 | Platform::SuperChip10 | Platform::SuperChip11 => {
+                let instruction_set = match platform {
+                    Platform::Chip48 => InstructionSet::Chip8,
+                    Platform::SuperChip10 => InstructionSet::SuperChip10,
+                    _ => InstructionSet::SuperChip11,
+                };
+                Self {
+                    instruction_delay: instruction_delay_from_speed(30),
+                    instructions_per_frame: 30,
+                    key_held_plays_sound: false,
+                    wait_for_key_release: false,
+                    use_assembly_routine: false,
+                    use_variable_offset: true,
+                    increment_on_store: false,
+                    shift_uses_y_register: false,
+                    logic_resets_flag: false,
+                    clip_sprites: true,
+                    draw_waits_for_frame: false,
+                    instruction_set,
+                    ..configuration
+                }
+            }
+            Platform::XoChip => Self {
+                instruction_delay: instruction_delay_from_speed(1000),
+                instructions_per_frame: 1000,
+                memory_size: XO_CHIP_MEMORY_SIZE,
+                key_held_plays_sound: false,
+                wait_for_key_release: false,
+                use_assembly_routine: false,
+                use_variable_offset: false,
+                increment_on_store: true,
+                shift_uses_y_register: true,
+                logic_resets_flag: false,
+                clip_sprites: false,
+                draw_waits_for_frame: false,
+                instruction_set: InstructionSet::XoChip,
+                display_plane_count: XO_CHIP_DISPLAY_PLANE_COUNT,
+                ..configuration
+            },
+        }
+    }
+}
+// encoding
+impl Configuration {
+    /// Appends every field to a [SaveState](super::SaveState) or a [Movie](super::Movie).
+    pub(crate) fn encode(&self, writer: &mut Writer) {
+        writer.u64(self.instruction_delay.as_nanos() as u64);
+        writer.usize(self.instructions_per_frame);
+        writer.usize(self.memory_size);
+        writer.bool(self.key_held_plays_sound);
+        writer.bool(self.wait_for_key_release);
+        writer.bool(self.use_assembly_routine);
+        writer.bool(self.use_variable_offset);
+        writer.bool(self.increment_on_store);
+        writer.bool(self.shift_uses_y_register);
+        writer.bool(self.logic_resets_flag);
+        writer.bool(self.clip_sprites);
+        writer.bool(self.draw_waits_for_frame);
+        writer.u8(self.instruction_set as u8);
+        writer.usize(self.program_start);
+        writer.resolution(self.initial_resolution);
+        writer.usize(self.display_plane_count);
+        writer.u64(self.random_seed);
+        writer.bytes(&self.font_data);
+        writer.usize(self.font_data_start);
+        writer.usize(self.font_data_end);
+        writer.bytes(&self.big_font_data);
+        writer.usize(self.big_font_data_start);
+        writer.usize(self.big_font_data_end);
+    }
+
+    /// Reads the fields appended by [Configuration::encode].
+    /// Returns an error for configurations that [ConfigurationBuilder::build] can not build.
+    pub(crate) fn decode(reader: &mut Reader) -> Result<Self, SaveStateError> {
+        let configuration = Self {
+            instruction_delay: Duration::from_nanos(reader.u64()?),
+            instructions_per_frame: reader.usize()?,
+            memory_size: reader.usize()?,
+            key_held_plays_sound: reader.bool()?,
+            wait_for_key_release: reader.bool()?,
+            use_assembly_routine: reader.bool()?,
+            use_variable_offset: reader.bool()?,
+            increment_on_store: reader.bool()?,
+            shift_uses_y_register: reader.bool()?,
+            logic_resets_flag: reader.bool()?,
+            clip_sprites: reader.bool()?,
+            draw_waits_for_frame: reader.bool()?,
+            instruction_set: match reader.u8()? {
+                0 => InstructionSet::Chip8,
+                1 => InstructionSet::SuperChip10,
+                2 => InstructionSet::SuperChip11,
+                3 => InstructionSet::XoChip,
+                _ => return Err(SaveStateError::InvalidValue),
+            },
+            program_start: reader.usize()?,
+            initial_resolution: reader.resolution()?,
+            display_plane_count: reader.usize()?,
+            random_seed: reader.u64()?,
+            font_data: reader.array()?,
+            font_data_start: reader.usize()?,
+            font_data_end: reader.usize()?,
+            big_font_data: reader.array()?,
+            big_font_data_start: reader.usize()?,
+            big_font_data_end: reader.usize()?,
+        };
+
+        let font_fits = |start: usize, end: usize, length: usize| {
+            end.checked_sub(start) == Some(length - 1) && end < configuration.memory_size
+        };
+        let is_valid = font_fits(
+            configuration.font_data_start,
+            configuration.font_data_end,
+            configuration.font_data.len(),
+        ) && font_fits(
+            configuration.big_font_data_start,
+            configuration.big_font_data_end,
+            configuration.big_font_data.len(),
+        ) && configuration.program_start <= configuration.memory_size
+            && (1..=Display::MAX_PLANE_COUNT).contains(&configuration.display_plane_count);
+        match is_valid {
+            true => Ok(configuration),
+            false => Err(SaveStateError::InvalidValue),
+        }
+    }
+}
 impl Default for Configuration {
     fn default() -> Self {
         Self::new()
     }
 }
+impl From<Configuration> for ConfigurationBuilder {
+    fn from(configuration: Configuration) -> Self {
+        Self(configuration)
+    }
+}
 impl ConfigurationBuilder {
     pub const fn new() -> Self {
         Self(Configuration::new())
     }
+    /// Starts from the [Configuration::preset] of the given [Platform].
+    pub const fn preset(platform: Platform) -> Self {
+        Self(Configuration::preset(platform))
+    }
+    /// Picks a random seed for normal play. The seed is part of the [Configuration],
+    /// so movies recorded with it still replay the same random numbers.
+    pub fn random_seed_from_entropy(mut self) -> Self {
+        self.0.random_seed = rand::random::<u64>().max(1);
+        self
+    }
     pub fn build(self) -> Interpreter {
+        let random_seed = self.0.random_seed;
+        self.build_with_random_source(XorShiftRandomSource::new(random_seed))
+    }
+    /// Builds an [Interpreter] whose `Cxkk` uses `random_source` instead of the
+    /// [XorShiftRandomSource] seeded with [Configuration::random_seed].
+    ///
+    /// Movies only replay identically with the default source.
+    pub fn build_with_random_source(
+        self,
+        random_source: impl RandomSource + 'static,
+    ) -> Interpreter {
         let mut memory = vec![0; self.0.memory_size].into_boxed_slice();
         memory[self.0.font_data_start..=self.0.font_data_end].copy_from_slice(&self.0.font_data);
+        memory[self.0.big_font_data_start..=self.0.big_font_data_end]
+            .copy_from_slice(&self.0.big_font_data);
 
         Interpreter {
             memory,
@@ -73,18 +376,53 @@ impl ConfigurationBuilder {
             variable_register: [0; 16],
             call_stack: [0; 16],
             call_stack_index: 0,
+            flag_registers: [0; 16],
             delay_timer: 0,
             sound_timer: 0,
-            last_timer_tick: Instant::now(),
-            last_instruction_time: Instant::now(),
-            random_state: 0x13275389,
-            display: vec![
-                vec![false; self.0.display_width].into_boxed_slice();
-                self.0.display_height
-            ]
-            .into_boxed_slice(),
+            key_awaiting_release: None,
+            vertical_blank: false,
+            program_hash: program_hash(&[]),
+            clock: Box::new(RealTimeClock::new()),
+            last_timer_tick: Duration::ZERO,
+            last_instruction_time: Duration::ZERO,
+            random_source: Box::new(random_source),
+            symbol_table: None,
+            memory_accesses: None,
+            display: Display::with_resolution(
+                self.0.initial_resolution,
+                self.0.display_plane_count,
+            ),
+            selected_planes: 1,
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            resolution: self.0.initial_resolution,
+            resolution_changed: false,
+            exited: false,
             keypad: [false; 16],
             configuration: self.0,
         }
     }
 }
+
+#[test]
+fn test_presets() {
+    let cosmac_vip = Configuration::preset(Platform::CosmacVip);
+    assert!(cosmac_vip.shift_uses_y_register());
+    assert!(!cosmac_vip.use_variable_offset());
+    assert_eq!(cosmac_vip.initial_resolution(), Resolution::Low);
+    assert!(cosmac_vip.wait_for_key_release());
+
+    let super_chip = Configuration::preset(Platform::SuperChip11);
+    assert!(super_chip.use_variable_offset());
+    assert_eq!(super_chip.instruction_set(), InstructionSet::SuperChip11);
+    assert!(!super_chip.wait_for_key_release());
+
+    let xo_chip = ConfigurationBuilder::preset(Platform::XoChip).build();
+    assert_eq!(xo_chip.memory.len(), XO_CHIP_MEMORY_SIZE);
+}
+#[test]
+fn test_random_seed_from_entropy() {
+    let configuration = ConfigurationBuilder::new().random_seed_from_entropy().0;
+    assert_ne!(configuration.random_seed(), 0);
+    assert_eq!(Configuration::new().random_seed(), DEFAULT_RANDOM_SEED);
+}
@@ -0,0 +1,96 @@
+use crate::interpreter::builder::TIMER_INTERVAL;
+use std::time::{Duration, Instant};
+
+/// The source of time used by [Interpreter::execute_current_instruction](super::Interpreter::execute_current_instruction)
+/// to tick the timers and to throttle instructions.
+pub trait Clock: std::fmt::Debug + Send {
+    /// The time passed since the clock was created.
+    fn now(&self) -> Duration;
+
+    /// Waits until [Clock::now] reaches `deadline`.
+    fn sleep_until(&mut self, deadline: Duration);
+
+    /// Called after every executed instruction.
+    fn cycle(&mut self) {}
+}
+
+/// Follows the wall clock and sleeps the current thread. This is the default [Clock].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RealTimeClock {
+    start: Instant,
+}
+impl RealTimeClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+impl Default for RealTimeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Clock for RealTimeClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&mut self, deadline: Duration) {
+        let now = self.now();
+        if now < deadline {
+            std::thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// Derives time from the number of executed instructions so that identical inputs produce identical runs.
+///
+/// Every `instructions_per_frame` instructions one frame (1/60s) passes. Sleeping returns immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VirtualClock {
+    instructions_per_frame: u64,
+    cycles: u64,
+}
+impl VirtualClock {
+    /// # Panics
+    /// If `instructions_per_frame` is zero
+    pub fn new(instructions_per_frame: u64) -> Self {
+        assert!(
+            instructions_per_frame > 0,
+            "a frame must have at least one instruction"
+        );
+        Self {
+            instructions_per_frame,
+            cycles: 0,
+        }
+    }
+
+    /// The number of instructions executed since the clock was created.
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        let nanos =
+            self.cycles as u128 * TIMER_INTERVAL.as_nanos() / self.instructions_per_frame as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    fn sleep_until(&mut self, _deadline: Duration) {}
+
+    fn cycle(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+#[test]
+fn test_virtual_clock_frames() {
+    let mut clock = VirtualClock::new(3);
+    for _ in 0..3 {
+        assert!(clock.now() < TIMER_INTERVAL);
+        clock.cycle();
+    }
+    assert_eq!(clock.now(), TIMER_INTERVAL);
+}
@@ -0,0 +1,627 @@
+use crate::instruction::Instruction;
+use crate::interpreter::{ExecutionError, Interpreter, StepOutcome};
+use std::{collections::BTreeMap, ops::Range, str::FromStr};
+
+/// The frames [Debugger::step_over] and [Debugger::step_out] run before giving up on a subroutine returning.
+pub const STEP_FRAME_LIMIT: u64 = 60 * 60;
+
+/// Whether an instruction read or wrote memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryAccessKind {
+    Read,
+    Write,
+}
+
+/// The memory an instruction read or wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryAccess {
+    pub kind: MemoryAccessKind,
+    pub range: Range<usize>,
+}
+
+/// Stops a [Debugger] after an instruction reads or writes any byte of `range`.
+///
+/// Only `Dxyn`, `5xy2`, `5xy3`, `F002`, `Fx33`, `Fx55` and `Fx65` access memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Watchpoint {
+    pub range: Range<usize>,
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+// initialization
+impl Watchpoint {
+    pub const fn read(range: Range<usize>) -> Self {
+        Self {
+            range,
+            on_read: true,
+            on_write: false,
+        }
+    }
+
+    pub const fn write(range: Range<usize>) -> Self {
+        Self {
+            range,
+            on_read: false,
+            on_write: true,
+        }
+    }
+
+    /// Watches both reads and writes.
+    pub const fn access(range: Range<usize>) -> Self {
+        Self {
+            range,
+            on_read: true,
+            on_write: true,
+        }
+    }
+}
+
+// accessors
+impl Watchpoint {
+    pub fn matches(&self, access: &MemoryAccess) -> bool {
+        let kind_matches = match access.kind {
+            MemoryAccessKind::Read => self.on_read,
+            MemoryAccessKind::Write => self.on_write,
+        };
+        kind_matches && access.range.start < self.range.end && self.range.start < access.range.end
+    }
+}
+
+/// Reads an uppercase decimal, `0X` hexadecimal or `0B` binary number.
+fn parse_number(text: &str) -> Option<u16> {
+    if let Some(hexadecimal) = text.strip_prefix("0X") {
+        u16::from_str_radix(hexadecimal, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0B") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// A value compared by a [Condition].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    /// `VX`
+    Register(u8),
+    /// `I`
+    AddressRegister,
+    /// `DT`
+    DelayTimer,
+    /// `ST`
+    SoundTimer,
+    Value(u16),
+}
+impl Operand {
+    fn value(self, interpreter: &Interpreter) -> u16 {
+        match self {
+            Self::Register(index) => interpreter.variable_register[index as usize & 0xF] as u16,
+            Self::AddressRegister => interpreter.address_register,
+            Self::DelayTimer => interpreter.delay_timer as u16,
+            Self::SoundTimer => interpreter.sound_timer as u16,
+            Self::Value(value) => value,
+        }
+    }
+}
+impl FromStr for Operand {
+    type Err = ConditionError;
+
+    /// Reads `V0` to `VF`, `I`, `DT`, `ST` or a decimal, `0x` hexadecimal or `0b` binary number.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let upper = text.to_ascii_uppercase();
+        let value = match upper.as_str() {
+            "I" => return Ok(Self::AddressRegister),
+            "DT" => return Ok(Self::DelayTimer),
+            "ST" => return Ok(Self::SoundTimer),
+            register if register.len() == 2 && register.starts_with('V') => {
+                u8::from_str_radix(&register[1..], 16)
+                    .map(Self::Register)
+                    .ok()
+            }
+            number => parse_number(number).map(Self::Value),
+        };
+        value.ok_or_else(|| ConditionError {
+            message: format!("expected a register, I, DT, ST or a number but found `{text}`"),
+        })
+    }
+}
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Register(index) => write!(f, "V{index:X}"),
+            Self::AddressRegister => write!(f, "I"),
+            Self::DelayTimer => write!(f, "DT"),
+            Self::SoundTimer => write!(f, "ST"),
+            Self::Value(value) => write!(f, "{value:#X}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+impl Comparison {
+    /// Two character operators come first so `<=` is not read as `<`.
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| *comparison == self)
+            .map(|(symbol, _)| *symbol)
+            .unwrap_or_default()
+    }
+
+    pub fn compare(self, left: u16, right: u16) -> bool {
+        match self {
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// An error in the text given to [Condition::from_str].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConditionError {
+    pub message: String,
+}
+impl std::fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for ConditionError {}
+
+/// The condition of a conditional breakpoint, parsed from text like `V3 == 0x10` or `I >= 0x300`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Condition {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+impl Condition {
+    pub fn is_met(&self, interpreter: &Interpreter) -> bool {
+        self.comparison
+            .compare(self.left.value(interpreter), self.right.value(interpreter))
+    }
+}
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (left, comparison, right) = Comparison::OPERATORS
+            .iter()
+            .find_map(|&(symbol, comparison)| {
+                let (left, right) = text.split_once(symbol)?;
+                Some((left, comparison, right))
+            })
+            .ok_or_else(|| ConditionError {
+                message: "expected one of ==, !=, <=, >=, < or >".to_owned(),
+            })?;
+        Ok(Self {
+            left: left.trim().parse()?,
+            comparison,
+            right: right.trim().parse()?,
+        })
+    }
+}
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.left,
+            self.comparison.symbol(),
+            self.right
+        )
+    }
+}
+
+/// Why a [Debugger] stopped executing instructions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    /// The instruction at `address` has a breakpoint and its condition is met. It was not executed.
+    Breakpoint { address: u16 },
+
+    /// The instruction at `address` accessed memory covered by a [Watchpoint]. It was executed.
+    Watchpoint { address: u16, access: MemoryAccess },
+
+    /// The step finished.
+    Step,
+
+    /// The requested frame has been reached.
+    /// [Debugger::step_over] and [Debugger::step_out] also stop here after [STEP_FRAME_LIMIT] frames.
+    FrameComplete,
+
+    /// `Fx0A` is waiting for a key press during a step. Press a key and step again.
+    WaitingForKey,
+
+    /// `00FD` ended the program.
+    Exited,
+
+    /// The instruction at the `program_counter` failed.
+    Error(ExecutionError),
+}
+
+/// Runs an [Interpreter] one frame at a time like [Interpreter::run_frame],
+/// stopping at breakpoints and watchpoints and stepping through subroutines.
+///
+/// A frame interrupted by a stop is finished by the next call, so the timers still tick once every
+/// [Configuration::instructions_per_frame](super::Configuration::instructions_per_frame) instructions.
+/// Execution never stops at a breakpoint on the instruction it resumes from.
+#[derive(Debug)]
+pub struct Debugger {
+    interpreter: Interpreter,
+
+    /// Breakpoints by address. Breakpoints without a condition always stop.
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+
+    /// The number of frames completed.
+    frame: u64,
+    /// The instructions executed since the start of the current frame.
+    cycles_in_frame: usize,
+}
+
+// initialization
+impl Debugger {
+    pub fn new(mut interpreter: Interpreter) -> Self {
+        interpreter.memory_accesses = Some(Vec::new());
+        Self {
+            interpreter,
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            frame: 0,
+            cycles_in_frame: 0,
+        }
+    }
+}
+
+// accessors
+impl Debugger {
+    pub const fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// Returns the interpreter and stops recording memory accesses.
+    pub fn into_interpreter(mut self) -> Interpreter {
+        self.interpreter.memory_accesses = None;
+        self.interpreter
+    }
+
+    /// The number of frames completed since the debugger was created.
+    pub const fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns every breakpoint address with its condition.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u16, Option<&Condition>)> {
+        self.breakpoints
+            .iter()
+            .map(|(&address, condition)| (address, condition.as_ref()))
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Returns `stop_reason` as a line for a front-end, naming addresses with [Interpreter::describe_address].
+    /// Errors are described with [Interpreter::describe_error].
+    pub fn describe_stop(&self, stop_reason: &StopReason) -> String {
+        let interpreter = &self.interpreter;
+        match stop_reason {
+            StopReason::Breakpoint { address } => {
+                format!("breakpoint at {}", interpreter.describe_address(*address))
+            }
+            StopReason::Watchpoint { address, access } => {
+                let kind = match access.kind {
+                    MemoryAccessKind::Read => "read",
+                    MemoryAccessKind::Write => "write",
+                };
+                format!(
+                    "{kind} of {:#05X}..{:#05X} at {}",
+                    access.range.start,
+                    access.range.end,
+                    interpreter.describe_address(*address)
+                )
+            }
+            StopReason::Step => format!(
+                "stepped to {}",
+                interpreter.describe_address(interpreter.program_counter)
+            ),
+            StopReason::FrameComplete => format!("completed frame {}", self.frame),
+            StopReason::WaitingForKey => format!(
+                "waiting for a key at {}",
+                interpreter.describe_address(interpreter.program_counter)
+            ),
+            StopReason::Exited => "the program exited".to_owned(),
+            StopReason::Error(error) => interpreter.describe_error(error),
+        }
+    }
+
+    /// Returns `true` if a breakpoint at `address` would stop execution now.
+    fn is_breakpoint_hit(&self, address: u16) -> bool {
+        match self.breakpoints.get(&address) {
+            Some(Some(condition)) => condition.is_met(&self.interpreter),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Returns the first memory access of the last instruction covered by a watchpoint.
+    fn watchpoint_hit(&self) -> Option<&MemoryAccess> {
+        let memory_accesses = self.interpreter.memory_accesses.as_deref()?;
+        memory_accesses.iter().find(|access| {
+            self.watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.matches(access))
+        })
+    }
+}
+
+// mutators
+impl Debugger {
+    /// Changes to the interpreter are fine while stopped. Prefer [Debugger::run_frame] over running it directly.
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address, None);
+    }
+
+    /// Stops at `address` only when `condition` is met before the instruction executes.
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Condition) {
+        self.breakpoints.insert(address, Some(condition));
+    }
+
+    /// Returns `true` if there was a breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Returns `true` if `watchpoint` was being watched.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let length = self.watchpoints.len();
+        self.watchpoints.retain(|watched| watched != watchpoint);
+        self.watchpoints.len() != length
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Executes one instruction, finishing the frame first if a `Dxyn` waits for it.
+    pub fn step_into(&mut self) -> StopReason {
+        self.run(self.frame + STEP_FRAME_LIMIT, Some(&|_: &Interpreter| true))
+    }
+
+    /// Executes one instruction. A subroutine call runs until the subroutine returns.
+    pub fn step_over(&mut self) -> StopReason {
+        let depth = self.interpreter.call_stack_index;
+        let is_call = self
+            .interpreter
+            .get_current_opcode()
+            .and_then(|opcode| self.interpreter.decode(opcode))
+            .is_some_and(|instruction| matches!(instruction, Instruction::CallSubroutine { .. }));
+        if !is_call {
+            return self.step_into();
+        }
+        self.run(
+            self.frame + STEP_FRAME_LIMIT,
+            Some(&|interpreter: &Interpreter| interpreter.call_stack_index <= depth),
+        )
+    }
+
+    /// Runs until the current subroutine returns. Outside of a subroutine this is [Debugger::step_into].
+    pub fn step_out(&mut self) -> StopReason {
+        let depth = self.interpreter.call_stack_index;
+        if depth == 0 {
+            return self.step_into();
+        }
+        self.run(
+            self.frame + STEP_FRAME_LIMIT,
+            Some(&|interpreter: &Interpreter| interpreter.call_stack_index < depth),
+        )
+    }
+
+    /// Finishes the current frame like [Interpreter::run_frame].
+    pub fn run_frame(&mut self) -> StopReason {
+        self.run_until_frame(self.frame + 1)
+    }
+
+    /// Runs frames until [Debugger::frame] reaches `frame`. `Fx0A` waiting for a key does not stop execution.
+    pub fn run_until_frame(&mut self, frame: u64) -> StopReason {
+        self.run(frame, None)
+    }
+
+    /// Executes instructions until `frame` is reached or `is_finished` returns `true` after an instruction.
+    ///
+    /// Steps pass `is_finished` and stop when `Fx0A` waits for a key.
+    fn run(
+        &mut self,
+        frame: u64,
+        is_finished: Option<&dyn Fn(&Interpreter) -> bool>,
+    ) -> StopReason {
+        let mut skipped_breakpoint = Some(self.interpreter.program_counter);
+        loop {
+            if self.frame >= frame {
+                return StopReason::FrameComplete;
+            }
+            if self.cycles_in_frame >= self.interpreter.configuration.instructions_per_frame() {
+                self.end_frame();
+                continue;
+            }
+
+            let address = self.interpreter.program_counter;
+            if skipped_breakpoint != Some(address) && self.is_breakpoint_hit(address) {
+                return StopReason::Breakpoint { address };
+            }
+
+            match self.interpreter.step() {
+                Err(error) => return StopReason::Error(error),
+                Ok(StepOutcome::Exited) => return StopReason::Exited,
+                Ok(StepOutcome::WaitingForFrame) => self.end_frame(),
+                Ok(StepOutcome::WaitingForKey) => {
+                    self.cycles_in_frame += 1;
+                    if is_finished.is_some() {
+                        return StopReason::WaitingForKey;
+                    }
+                }
+                Ok(StepOutcome::Executed(_)) => {
+                    self.cycles_in_frame += 1;
+                    skipped_breakpoint = None;
+                    if let Some(access) = self.watchpoint_hit() {
+                        let access = access.clone();
+                        return StopReason::Watchpoint { address, access };
+                    }
+                    if is_finished.is_some_and(|is_finished| is_finished(&self.interpreter)) {
+                        return StopReason::Step;
+                    }
+                }
+            }
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.interpreter.tick_timers();
+        self.frame += 1;
+        self.cycles_in_frame = 0;
+    }
+}
+
+#[cfg(test)]
+fn debugger_for(source: &str) -> (Debugger, crate::asm::Assembly) {
+    let assembly = crate::asm::Assembly::new(source).unwrap();
+    let mut interpreter = Interpreter::default();
+    interpreter
+        .load_program_from_bytes(assembly.bytes())
+        .unwrap();
+    (Debugger::new(interpreter), assembly)
+}
+#[cfg(test)]
+const COUNTER_SOURCE: &str = "\
+: main
+    v3 := 0
+: loop
+    v3 += 1
+    count
+    jump loop
+: count
+    i := counter
+    save v3
+    return
+: counter
+    0
+";
+
+#[test]
+fn test_breakpoints() {
+    let (mut debugger, assembly) = debugger_for(COUNTER_SOURCE);
+    let count = assembly.label("count").unwrap();
+
+    let stop = StopReason::Breakpoint { address: count };
+    debugger.add_breakpoint(count);
+    assert_eq!(debugger.run_until_frame(10), stop);
+    assert_eq!(debugger.interpreter().registers()[3], 1);
+    assert_eq!(debugger.run_until_frame(10), stop);
+    assert_eq!(debugger.interpreter().registers()[3], 2);
+
+    debugger.add_conditional_breakpoint(count, "v3 == 0x10".parse().unwrap());
+    assert_eq!(debugger.run_until_frame(10), stop);
+    assert_eq!(debugger.interpreter().registers()[3], 0x10);
+    assert!(debugger.frame() > 0);
+
+    assert!(debugger.remove_breakpoint(count));
+    assert_eq!(debugger.run_until_frame(10), StopReason::FrameComplete);
+    assert_eq!(debugger.frame(), 10);
+}
+#[test]
+fn test_watchpoints() {
+    let (mut debugger, assembly) = debugger_for(COUNTER_SOURCE);
+    let counter = assembly.label("counter").unwrap() as usize;
+    let save = assembly.label("count").unwrap() + 2;
+
+    debugger.add_watchpoint(Watchpoint::read(counter..counter + 1));
+    assert_eq!(debugger.run_frame(), StopReason::FrameComplete);
+
+    debugger.add_watchpoint(Watchpoint::write(counter..counter + 1));
+    let access = MemoryAccess {
+        kind: MemoryAccessKind::Write,
+        range: counter..counter + 4,
+    };
+    let stop_reason = debugger.run_frame();
+    assert_eq!(
+        stop_reason,
+        StopReason::Watchpoint {
+            address: save,
+            access
+        }
+    );
+    assert_eq!(debugger.interpreter().program_counter(), save + 2);
+    assert_eq!(
+        debugger.describe_stop(&stop_reason),
+        "write of 0x20E..0x212 at 0x20A"
+    );
+}
+#[test]
+fn test_stepping() {
+    let (mut debugger, assembly) = debugger_for(COUNTER_SOURCE);
+    let count = assembly.label("count").unwrap();
+    let loop_address = assembly.label("loop").unwrap();
+
+    assert_eq!(debugger.step_into(), StopReason::Step);
+    assert_eq!(debugger.step_over(), StopReason::Step);
+    assert_eq!(debugger.interpreter().program_counter(), loop_address + 2);
+    assert_eq!(debugger.step_over(), StopReason::Step);
+    assert_eq!(debugger.interpreter().program_counter(), loop_address + 4);
+    assert_eq!(debugger.interpreter().call_stack().len(), 0);
+
+    debugger.add_breakpoint(count);
+    assert_eq!(debugger.step_into(), StopReason::Step);
+    assert_eq!(debugger.step_into(), StopReason::Step);
+    assert_eq!(debugger.step_into(), StopReason::Step);
+    assert_eq!(debugger.interpreter().program_counter(), count);
+    assert_eq!(debugger.step_into(), StopReason::Step);
+    assert_eq!(debugger.step_out(), StopReason::Step);
+    assert_eq!(debugger.interpreter().program_counter(), loop_address + 4);
+}
+#[test]
+fn test_parse_condition() {
+    let condition: Condition = "I>=0x300".parse().unwrap();
+    assert_eq!(condition.to_string(), "I >= 0x300");
+    assert_eq!(
+        "dt < 0b10".parse::<Condition>().unwrap().to_string(),
+        "DT < 0x2"
+    );
+    assert_eq!(
+        "VG == 1".parse::<Condition>().unwrap_err().to_string(),
+        "expected a register, I, DT, ST or a number but found `VG`"
+    );
+    assert!("V3 = 1".parse::<Condition>().is_err());
+}
@@ -0,0 +1,203 @@
+use crate::interpreter::builder::{
+    DEFAULT_DISPLAY_HEIGHT, DEFAULT_DISPLAY_WIDTH, HIGH_RESOLUTION_DISPLAY_HEIGHT,
+    HIGH_RESOLUTION_DISPLAY_WIDTH,
+};
+
+/// The display sizes a program can switch between with `00FE` and `00FF`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    /// 64x32 pixels
+    #[default]
+    Low,
+    /// 128x64 pixels
+    High,
+}
+impl Resolution {
+    pub const fn width(self) -> usize {
+        match self {
+            Self::Low => DEFAULT_DISPLAY_WIDTH,
+            Self::High => HIGH_RESOLUTION_DISPLAY_WIDTH,
+        }
+    }
+
+    pub const fn height(self) -> usize {
+        match self {
+            Self::Low => DEFAULT_DISPLAY_HEIGHT,
+            Self::High => HIGH_RESOLUTION_DISPLAY_HEIGHT,
+        }
+    }
+}
+
+/// The pixels of the chip8 display.
+///
+/// Each pixel is a color index made of one bit per plane.
+/// Plane 0 is the least significant bit. A color index of 0 is an unlit pixel.
+/// Plain chip8 and SUPER-CHIP only use plane 0. XO-CHIP can use up to four planes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Display {
+    width: usize,
+    height: usize,
+    plane_count: usize,
+
+    /// Color indexes in row-major order
+    pixels: Box<[u8]>,
+}
+
+// initialization
+impl Display {
+    pub const MAX_PLANE_COUNT: usize = 4;
+
+    /// Creates a display of the given [Resolution] with every pixel unlit.
+    ///
+    /// # Panics
+    /// If `plane_count` is zero or more than [Display::MAX_PLANE_COUNT]
+    pub fn with_resolution(resolution: Resolution, plane_count: usize) -> Self {
+        Self::new(resolution.width(), resolution.height(), plane_count)
+    }
+
+    /// Creates a display with every pixel unlit.
+    ///
+    /// # Panics
+    /// If `plane_count` is zero or more than [Display::MAX_PLANE_COUNT]
+    pub fn new(width: usize, height: usize, plane_count: usize) -> Self {
+        assert!(
+            (1..=Self::MAX_PLANE_COUNT).contains(&plane_count),
+            "a display must have between 1 and {} planes",
+            Self::MAX_PLANE_COUNT
+        );
+        Self {
+            width,
+            height,
+            plane_count,
+            pixels: vec![0; width * height].into_boxed_slice(),
+        }
+    }
+}
+
+// accessors
+impl Display {
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub const fn plane_count(&self) -> usize {
+        self.plane_count
+    }
+
+    /// A mask with one bit set for every plane of this display.
+    pub const fn all_planes(&self) -> u8 {
+        (1 << self.plane_count) - 1
+    }
+
+    /// The color index of every pixel in row-major order.
+    pub fn color_indexes(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Each row of color indexes from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks_exact(self.width.max(1))
+    }
+
+    /// Returns the color index of the pixel at (`x`, `y`).
+    ///
+    /// # Panics
+    /// If (`x`, `y`) is outside of the display
+    pub fn color_index(&self, x: usize, y: usize) -> u8 {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Returns `true` if the pixel at (`x`, `y`) is lit in any plane.
+    ///
+    /// # Panics
+    /// If (`x`, `y`) is outside of the display
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.color_index(x, y) != 0
+    }
+
+    /// Returns `true` if the pixel at (`x`, `y`) is lit in `plane`.
+    ///
+    /// # Panics
+    /// If (`x`, `y`) is outside of the display
+    pub fn plane_pixel(&self, plane: usize, x: usize, y: usize) -> bool {
+        self.color_index(x, y) & (1 << plane) != 0
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        y * self.width + x
+    }
+}
+
+// mutators
+impl Display {
+    /// Flips the pixel at (`x`, `y`) in every plane of `plane_mask`.
+    /// Returns `true` if the pixel was lit in any of those planes before flipping.
+    pub(crate) fn toggle_pixel(&mut self, x: usize, y: usize, plane_mask: u8) -> bool {
+        let index = self.index(x, y);
+        let pixel = &mut self.pixels[index];
+        let collision = *pixel & plane_mask != 0;
+        *pixel ^= plane_mask;
+        collision
+    }
+
+    /// The color index of every pixel in row-major order.
+    pub(crate) fn color_indexes_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    /// Unlights every pixel in the planes of `plane_mask`.
+    pub(crate) fn clear(&mut self, plane_mask: u8) {
+        self.pixels.iter_mut().for_each(|p| *p &= !plane_mask);
+    }
+
+    /// Replaces the display with an unlit display of a new [Resolution].
+    pub(crate) fn resize(&mut self, resolution: Resolution) {
+        *self = Self::with_resolution(resolution, self.plane_count);
+    }
+
+    /// Moves the planes of `plane_mask` by (`x_offset`, `y_offset`) pixels.
+    /// Pixels moved past an edge are lost. Pixels uncovered at the other edge are unlit.
+    pub(crate) fn scroll(&mut self, x_offset: isize, y_offset: isize, plane_mask: u8) {
+        let previous = self.pixels.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let source_x = x as isize - x_offset;
+                let source_y = y as isize - y_offset;
+                let source = if (0..self.width as isize).contains(&source_x)
+                    && (0..self.height as isize).contains(&source_y)
+                {
+                    previous[source_y as usize * self.width + source_x as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixels[y * self.width + x];
+                *pixel = (*pixel & !plane_mask) | (source & plane_mask);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_toggle_pixel_planes() {
+    let mut display = Display::new(4, 2, 2);
+    assert!(!display.toggle_pixel(1, 1, 0b01));
+    assert!(!display.toggle_pixel(1, 1, 0b10));
+    assert_eq!(display.color_index(1, 1), 0b11);
+    assert!(display.toggle_pixel(1, 1, 0b10));
+    assert_eq!(display.color_index(1, 1), 0b01);
+    assert!(display.plane_pixel(0, 1, 1));
+    assert!(!display.plane_pixel(1, 1, 1));
+}
+#[test]
+fn test_scroll_only_selected_planes() {
+    let mut display = Display::new(4, 2, 2);
+    display.toggle_pixel(0, 0, 0b11);
+    display.scroll(1, 1, 0b10);
+    assert_eq!(display.color_index(0, 0), 0b01);
+    assert_eq!(display.color_index(1, 1), 0b10);
+}
@@ -0,0 +1,64 @@
+/// The reasons [Interpreter::step](super::Interpreter::step) and
+/// [Interpreter::load_program_from_bytes](super::Interpreter::load_program_from_bytes) can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecutionError {
+    /// The opcode at `program_counter` does not match any instruction.
+    UnknownOpcode { program_counter: u16, opcode: u16 },
+
+    /// A subroutine was called while every entry in the call stack was in use.
+    StackOverflow,
+
+    /// A subroutine returned while the call stack was empty.
+    StackUnderflow,
+
+    /// An instruction tried to read or write memory past the end of memory.
+    MemoryOutOfBounds { address: usize },
+
+    /// The `program_counter` does not point to a complete opcode in memory.
+    ProgramCounterOutOfBounds { program_counter: u16 },
+
+    /// `0nnn` called a machine language routine while
+    /// [Configuration::use_assembly_routine](super::Configuration::use_assembly_routine) is enabled.
+    AssemblyRoutine { address: u16 },
+
+    /// A program of `program_size` bytes was loaded, but only `available` bytes of memory follow the program start.
+    ProgramTooLarge {
+        program_size: usize,
+        available: usize,
+    },
+}
+impl std::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownOpcode {
+                program_counter,
+                opcode,
+            } => write!(
+                f,
+                "unknown opcode {opcode:#06X} at address {program_counter:#05X}"
+            ),
+            Self::StackOverflow => write!(f, "call stack overflow"),
+            Self::StackUnderflow => write!(f, "return with an empty call stack"),
+            Self::MemoryOutOfBounds { address } => {
+                write!(f, "memory access out of bounds at address {address:#05X}")
+            }
+            Self::ProgramCounterOutOfBounds { program_counter } => {
+                write!(f, "program counter {program_counter:#05X} is out of bounds")
+            }
+            Self::AssemblyRoutine { address } => {
+                write!(
+                    f,
+                    "machine language routine at address {address:#05X} can not be executed"
+                )
+            }
+            Self::ProgramTooLarge {
+                program_size,
+                available,
+            } => write!(
+                f,
+                "the program is {program_size} bytes but only {available} bytes of memory are available"
+            ),
+        }
+    }
+}
+impl std::error::Error for ExecutionError {}
@@ -1,19 +1,116 @@
-use crate::interpreter::Interpreter;
+use crate::{
+    instruction::InstructionSet,
+    interpreter::{Display, ExecutionError, Interpreter, MemoryAccessKind, Resolution},
+};
+
+/// Returns the index of the register `offset` registers away from X in the direction of Y.
+fn register_range_index(x_register_index: usize, y_register_index: usize, offset: usize) -> usize {
+    if x_register_index <= y_register_index {
+        x_register_index + offset
+    } else {
+        x_register_index - offset
+    }
+}
 
 impl Interpreter {
+    /// Opcode: 0nnn
+    ///
+    /// Calls a machine language routine at the given address.
+    /// Ignored unless `use_assembly_routine` is enabled.
+    pub(super) fn assembly_routine(&mut self, address: u16) -> Result<(), ExecutionError> {
+        if self.configuration.use_assembly_routine() {
+            return Err(ExecutionError::AssemblyRoutine { address });
+        }
+        Ok(())
+    }
+
     /// Opcode: 00E0
     ///
     /// Clears the display.
+    /// XO-CHIP: only the selected planes are cleared.
     pub(super) fn clear_display(&mut self) {
-        self.display.iter_mut().flatten().for_each(|p| *p = false);
+        self.display.clear(self.selected_planes);
     }
 
     /// Opcode: 00EE
     ///
     /// Return from a subroutine.
-    pub(super) fn return_subroutine(&mut self) {
-        self.program_counter = self.call_stack[self.call_stack_index];
+    pub(super) fn return_subroutine(&mut self) -> Result<(), ExecutionError> {
+        if self.call_stack_index == 0 {
+            return Err(ExecutionError::StackUnderflow);
+        }
         self.call_stack_index -= 1;
+        self.program_counter = self.call_stack[self.call_stack_index];
+        Ok(())
+    }
+
+    /// Moves the `program_counter` past the next instruction.
+    /// XO-CHIP: `F000 nnnn` is skipped as a single four byte instruction.
+    fn skip_next_instruction(&mut self) {
+        let next_instruction_size = match self.get_current_opcode() {
+            Some(0xF000) if self.configuration.instruction_set() >= InstructionSet::XoChip => 4,
+            _ => 2,
+        };
+        self.program_counter = self.program_counter.wrapping_add(next_instruction_size);
+    }
+
+    /// Opcode: 00Cn (SUPER-CHIP 1.1)
+    ///
+    /// Scrolls the display down by `n` rows.
+    /// XO-CHIP: only the selected planes are scrolled.
+    pub(super) fn scroll_down(&mut self, rows: usize) {
+        self.display.scroll(0, rows as isize, self.selected_planes);
+    }
+
+    /// Opcode: 00Dn (XO-CHIP)
+    ///
+    /// Scrolls the selected planes up by `n` rows.
+    pub(super) fn scroll_up(&mut self, rows: usize) {
+        self.display
+            .scroll(0, -(rows as isize), self.selected_planes);
+    }
+
+    /// Opcode: 00FB (SUPER-CHIP 1.1)
+    ///
+    /// Scrolls the display right by 4 pixels.
+    /// XO-CHIP: only the selected planes are scrolled.
+    pub(super) fn scroll_right(&mut self) {
+        self.display.scroll(4, 0, self.selected_planes);
+    }
+
+    /// Opcode: 00FC (SUPER-CHIP 1.1)
+    ///
+    /// Scrolls the display left by 4 pixels.
+    /// XO-CHIP: only the selected planes are scrolled.
+    pub(super) fn scroll_left(&mut self) {
+        self.display.scroll(-4, 0, self.selected_planes);
+    }
+
+    /// Opcode: 00FD (SUPER-CHIP 1.0)
+    ///
+    /// Ends the program.
+    pub(super) fn exit(&mut self) {
+        self.exited = true;
+    }
+
+    /// Opcode: 00FE (SUPER-CHIP 1.0)
+    ///
+    /// Switches the display to 64x32 and clears it.
+    pub(super) fn low_resolution(&mut self) {
+        self.set_resolution(Resolution::Low);
+    }
+
+    /// Opcode: 00FF (SUPER-CHIP 1.0)
+    ///
+    /// Switches the display to 128x64 and clears it.
+    pub(super) fn high_resolution(&mut self) {
+        self.set_resolution(Resolution::High);
+    }
+
+    fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution_changed |= self.resolution != resolution;
+        self.resolution = resolution;
+        self.display.resize(resolution);
     }
 
     /// Opcode: 1nnn
@@ -26,10 +123,14 @@ impl Interpreter {
     /// Opcode: 2nnn
     ///
     /// Calls subroutine at given address.
-    pub(super) fn call_subroutine(&mut self, address: u16) {
-        self.call_stack_index += 1;
+    pub(super) fn call_subroutine(&mut self, address: u16) -> Result<(), ExecutionError> {
+        if self.call_stack_index == self.call_stack.len() {
+            return Err(ExecutionError::StackOverflow);
+        }
         self.call_stack[self.call_stack_index] = self.program_counter;
+        self.call_stack_index += 1;
         self.program_counter = address;
+        Ok(())
     }
 
     /// Opcode: 3xkk
@@ -38,7 +139,7 @@ impl Interpreter {
     /// `VX` is equal to last byte of the instruction
     pub(super) fn skip_if_equal_value(&mut self, x_register_index: usize, value: u8) {
         if self.variable_register[x_register_index] == value {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
@@ -48,7 +149,7 @@ impl Interpreter {
     /// `VX` is NOT equal to last byte of the instruction
     pub(super) fn skip_if_not_equal_value(&mut self, x_register_index: usize, value: u8) {
         if self.variable_register[x_register_index] != value {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
@@ -58,10 +159,52 @@ impl Interpreter {
     /// `VX` equals `VY`
     pub(super) fn skip_if_equal(&mut self, x_register_index: usize, y_register_index: usize) {
         if self.variable_register[x_register_index] == self.variable_register[y_register_index] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
+    /// Opcode: 5xy2 (XO-CHIP)
+    ///
+    /// Stores from `VX` to `VY` (including both) in memory, starting at address `I`.
+    /// The registers are stored in reverse order if X is greater than Y. `I` is left unmodified.
+    pub(super) fn store_register_range(
+        &mut self,
+        x_register_index: usize,
+        y_register_index: usize,
+    ) -> Result<(), ExecutionError> {
+        let register_count = x_register_index.abs_diff(y_register_index) + 1;
+        let range = self.memory_range(self.address_register as usize, register_count)?;
+        self.record_memory_access(MemoryAccessKind::Write, &range);
+
+        for offset in 0..register_count {
+            let register_index = register_range_index(x_register_index, y_register_index, offset);
+            self.memory[range.start + offset] = self.variable_register[register_index];
+        }
+
+        Ok(())
+    }
+
+    /// Opcode: 5xy3 (XO-CHIP)
+    ///
+    /// Fills from `VX` to `VY` (including both) with values from memory, starting at address `I`.
+    /// The registers are filled in reverse order if X is greater than Y. `I` is left unmodified.
+    pub(super) fn load_register_range(
+        &mut self,
+        x_register_index: usize,
+        y_register_index: usize,
+    ) -> Result<(), ExecutionError> {
+        let register_count = x_register_index.abs_diff(y_register_index) + 1;
+        let range = self.memory_range(self.address_register as usize, register_count)?;
+        self.record_memory_access(MemoryAccessKind::Read, &range);
+
+        for offset in 0..register_count {
+            let register_index = register_range_index(x_register_index, y_register_index, offset);
+            self.variable_register[register_index] = self.memory[range.start + offset];
+        }
+
+        Ok(())
+    }
+
     /// Opcode: 6xkk
     ///
     /// Sets `VX` to `value`
@@ -86,23 +229,35 @@ impl Interpreter {
 
     /// Opcode: 8xy1
     ///
-    /// Sets `VX` to (`VX` or `VY`) bitwise
+    /// Sets `VX` to (`VX` or `VY`) bitwise.
+    /// `VF` is reset to 0 if `logic_resets_flag` is enabled.
     pub(super) fn bitwise_or_assign(&mut self, x_register_index: usize, y_register_index: usize) {
         self.variable_register[x_register_index] |= self.variable_register[y_register_index];
+        self.reset_flag_after_logic();
     }
 
     /// Opcode: 8xy2
     ///
-    /// Sets `VX` to `VX` and `VY` (bitwise)
+    /// Sets `VX` to `VX` and `VY` (bitwise).
+    /// `VF` is reset to 0 if `logic_resets_flag` is enabled.
     pub(super) fn bitwise_and_assign(&mut self, x_register_index: usize, y_register_index: usize) {
         self.variable_register[x_register_index] &= self.variable_register[y_register_index];
+        self.reset_flag_after_logic();
     }
 
     /// Opcode: 8xy3
     ///
-    /// Sets `VX` to `VX` xor `VY` (bitwise)
+    /// Sets `VX` to `VX` xor `VY` (bitwise).
+    /// `VF` is reset to 0 if `logic_resets_flag` is enabled.
     pub(super) fn bitwise_xor_assign(&mut self, x_register_index: usize, y_register_index: usize) {
         self.variable_register[x_register_index] ^= self.variable_register[y_register_index];
+        self.reset_flag_after_logic();
+    }
+
+    fn reset_flag_after_logic(&mut self) {
+        if self.configuration.logic_resets_flag() {
+            self.variable_register[0xF] = 0;
+        }
     }
 
     /// Opcode: 8xy4
@@ -127,10 +282,12 @@ impl Interpreter {
 
     /// Opcode: 8xy6
     ///
-    /// Stores the least significant bit of `VX` in `variable_register[0xF]` and then shifts `VX` to the right by 1
+    /// Stores the least significant bit of `VX` in `variable_register[0xF]` and then shifts `VX` to the right by 1.
+    /// If `shift_uses_y_register` is enabled `VY` is shifted instead and the result is stored in `VX`.
     pub(super) fn right_shift_assign(&mut self, x_register_index: usize, y_register_index: usize) {
-        self.variable_register[0xF] = self.variable_register[x_register_index] & 0b00000001;
-        self.variable_register[x_register_index] >>= 1;
+        let value = self.variable_register[self.shift_source(x_register_index, y_register_index)];
+        self.variable_register[x_register_index] = value >> 1;
+        self.variable_register[0xF] = value & 0b00000001;
     }
 
     /// Opcode: 8xy7
@@ -145,10 +302,20 @@ impl Interpreter {
 
     /// Opcode: 8xyE
     ///
-    /// Stores the most significant bit of `VX` in `variable_register[0xF]` and then shifts `VX` to the left by 1
+    /// Stores the most significant bit of `VX` in `variable_register[0xF]` and then shifts `VX` to the left by 1.
+    /// If `shift_uses_y_register` is enabled `VY` is shifted instead and the result is stored in `VX`.
     pub(super) fn left_shift_assign(&mut self, x_register_index: usize, y_register_index: usize) {
-        self.variable_register[0xF] = (self.variable_register[x_register_index] & 0b10000000) >> 7;
-        self.variable_register[x_register_index] <<= 1;
+        let value = self.variable_register[self.shift_source(x_register_index, y_register_index)];
+        self.variable_register[x_register_index] = value << 1;
+        self.variable_register[0xF] = (value & 0b10000000) >> 7;
+    }
+
+    fn shift_source(&self, x_register_index: usize, y_register_index: usize) -> usize {
+        if self.configuration.shift_uses_y_register() {
+            y_register_index
+        } else {
+            x_register_index
+        }
     }
 
     /// Opcode: 9xy0
@@ -156,7 +323,7 @@ impl Interpreter {
     /// Skips the next instruction if `VX` does not equal `VY`
     pub(super) fn skip_if_not_equal(&mut self, x_register_index: usize, y_register_index: usize) {
         if self.variable_register[x_register_index] != self.variable_register[y_register_index] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
@@ -167,22 +334,24 @@ impl Interpreter {
         self.address_register = address
     }
 
-    /// Opcode: Bxnn
+    /// Opcode: Bnnn
     ///
-    /// Jumps to the address address plus `VX`
-    /// Bnnn: option to add from `V0` only.
+    /// Jumps to the address plus `V0`.
+    /// Bxnn: if `use_variable_offset` is enabled jumps to the address plus `VX` instead.
     pub(super) fn jump_offset(&mut self, x_register_index: usize, address: u16) {
-        self.program_counter = address + self.variable_register[x_register_index] as u16;
+        let offset_register_index = if self.configuration.use_variable_offset() {
+            x_register_index
+        } else {
+            0
+        };
+        self.program_counter = address + self.variable_register[offset_register_index] as u16;
     }
 
     /// Opcode: Cxkk
     ///
     /// Sets `VX` to the result of a bitwise and operation on a random number (Typically: 0 to 255) and `KK`
     pub(super) fn random_number_assign(&mut self, x_register_index: usize, value: u8) {
-        self.random_state ^= self.random_state << 13;
-        self.random_state ^= self.random_state >> 17;
-        self.random_state ^= self.random_state << 5;
-        let random_number: u8 = (self.random_state & 0xFF) as u8;
+        let random_number = self.random_source.next_byte();
 
         self.variable_register[x_register_index] = random_number & value;
     }
@@ -190,66 +359,143 @@ impl Interpreter {
     /// Opcode: Dxyn
     ///
     /// Draws a sprite at coordinate (`VX`, `VY`) that has a width of 8 pixels and a height of `sprite_height` pixels. Each row of 8 pixels is read as bit-coded starting from memory location ``I``; `variable_register[0xF]` is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn, and to 0 if that does not happen
+    ///
+    /// Dxy0 (SUPER-CHIP 1.0): draws a 16x16 sprite made of two bytes per row.
+    ///
+    /// XO-CHIP: the sprite is drawn to every selected plane. Each plane reads its own sprite from memory, one after another.
+    ///
+    /// The sprite wraps around the edges of the display unless `clip_sprites` is enabled.
+    /// Returns `false` without drawing if `draw_waits_for_frame` is enabled and the frame has already been drawn to.
     pub(super) fn draw_sprite(
         &mut self,
         x_register_index: usize,
         y_register_index: usize,
         sprite_height: u8,
-    ) {
-        let display_width = self.configuration.display_width();
-        let display_height = self.configuration.display_height();
+    ) -> Result<bool, ExecutionError> {
+        if self.configuration.draw_waits_for_frame() {
+            if !self.vertical_blank {
+                self.program_counter = self.program_counter.wrapping_sub(2);
+                return Ok(false);
+            }
+            self.vertical_blank = false;
+        }
+
+        let (sprite_width, sprite_height) = if sprite_height == 0
+            && self.configuration.instruction_set() >= InstructionSet::SuperChip10
+        {
+            (16, 16)
+        } else {
+            (8, sprite_height as usize)
+        };
+        let sprite_row_size = sprite_width / 8;
+
+        let clip_sprites = self.configuration.clip_sprites();
+        let display_width = self.display.width();
+        let display_height = self.display.height();
         let address_register = self.address_register as usize;
+        let sprite_size = sprite_row_size * sprite_height;
+        let selected_planes = self.selected_planes & self.display.all_planes();
+        let sprite_range = self.memory_range(
+            address_register,
+            sprite_size * selected_planes.count_ones() as usize,
+        )?;
+        self.record_memory_access(MemoryAccessKind::Read, &sprite_range);
         let x_position = self.variable_register[x_register_index] as usize % display_width;
         let y_position = self.variable_register[y_register_index] as usize % display_height;
 
         self.variable_register[0xF] = 0;
 
-        for sprite_row_index in 0..sprite_height as usize {
-            let display_row_index = (y_position + sprite_row_index) % display_height;
+        let planes =
+            (0..Display::MAX_PLANE_COUNT).filter(|plane| selected_planes & (1 << plane) != 0);
+        for (sprite_index, plane) in planes.enumerate() {
+            let sprite_start = sprite_range.start + sprite_index * sprite_size;
 
-            let sprite_byte = self.memory[address_register + sprite_row_index];
-
-            for sprite_column_index in 0..8 {
-                let display_column_index = (x_position + sprite_column_index) % display_width;
-
-                let pixel_bitmask = 0b10000000 >> sprite_column_index as u8;
-                let sprite_pixel = (sprite_byte & pixel_bitmask) > 0;
+            for sprite_row_index in 0..sprite_height {
+                if clip_sprites && y_position + sprite_row_index >= display_height {
+                    break;
+                }
+                let display_row_index = (y_position + sprite_row_index) % display_height;
 
-                let display_pixel = &mut self.display[display_row_index][display_column_index];
+                let sprite_row_start = sprite_start + sprite_row_index * sprite_row_size;
 
-                if sprite_pixel {
-                    if *display_pixel {
+                for sprite_column_index in 0..sprite_width {
+                    if clip_sprites && x_position + sprite_column_index >= display_width {
+                        break;
+                    }
+                    let display_column_index = (x_position + sprite_column_index) % display_width;
+
+                    let sprite_byte = self.memory[sprite_row_start + sprite_column_index / 8];
+                    let pixel_bitmask = 0b10000000 >> (sprite_column_index % 8) as u8;
+                    let sprite_pixel = (sprite_byte & pixel_bitmask) > 0;
+
+                    if sprite_pixel
+                        && self.display.toggle_pixel(
+                            display_column_index,
+                            display_row_index,
+                            1 << plane,
+                        )
+                    {
                         self.variable_register[0xF] = 1;
                     }
-
-                    *display_pixel ^= true;
                 }
             }
         }
+
+        Ok(true)
     }
 
     /// Opcode: Ex9E
     ///
-    /// Skips the next instruction if the key stored in `VX` is pressed
+    /// Skips the next instruction if the key stored in `VX` is pressed.
+    /// Only the lowest nibble of `VX` is used.
     pub(super) fn skip_on_key_pressed(&mut self, x_register_index: usize) {
-        let key = self.variable_register[x_register_index] as usize;
+        let key = self.variable_register[x_register_index] as usize & 0x0F;
 
         if self.keypad[key] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
     /// Opcode: ExA1
     ///
-    /// Skips the next instruction if the key stored in `VX` is NOT pressed
+    /// Skips the next instruction if the key stored in `VX` is NOT pressed.
+    /// Only the lowest nibble of `VX` is used.
     pub(super) fn skip_on_key_not_pressed(&mut self, x_register_index: usize) {
-        let key = self.variable_register[x_register_index] as usize;
+        let key = self.variable_register[x_register_index] as usize & 0x0F;
 
         if !self.keypad[key] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
+    /// Opcode: F000 nnnn (XO-CHIP)
+    ///
+    /// Sets `I` to the 16 bit address stored after the opcode and moves the `program_counter` past it.
+    pub(super) fn address_register_assign_long(&mut self) -> Result<(), ExecutionError> {
+        let address = self.memory_range(self.program_counter as usize, 2)?.start;
+        self.address_register =
+            u16::from_be_bytes([self.memory[address], self.memory[address + 1]]);
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(())
+    }
+
+    /// Opcode: Fn01 (XO-CHIP)
+    ///
+    /// Selects the display planes used by drawing, clearing and scrolling. Each bit of `n` selects one plane.
+    pub(super) fn select_planes(&mut self, planes: u8) {
+        self.selected_planes = planes & self.display.all_planes();
+    }
+
+    /// Opcode: F002 (XO-CHIP)
+    ///
+    /// Fills the audio pattern with the 16 bytes of memory starting at address `I`.
+    pub(super) fn store_audio_pattern(&mut self) -> Result<(), ExecutionError> {
+        let range = self.memory_range(self.address_register as usize, self.audio_pattern.len())?;
+        self.record_memory_access(MemoryAccessKind::Read, &range);
+        self.audio_pattern.copy_from_slice(&self.memory[range]);
+        Ok(())
+    }
+
     /// Opcode: Fx07
     ///
     /// Sets `VX` to the `value` of the `delay_timer`
@@ -259,16 +505,40 @@ impl Interpreter {
 
     /// Opcode: Fx0A
     ///
-    /// A key press is awaited, and then stored in `VX`
-    pub(super) fn wait_for_key_press(&mut self, x_register_index: usize) {
-        match self
-            .keypad
-            .iter()
-            .position(|&is_key_pressed| is_key_pressed)
-        {
-            Some(key) => self.variable_register[x_register_index] = key as u8,
-            None => self.program_counter -= 2,
+    /// A key press is awaited, and then stored in `VX` once the key is released.
+    /// Without `wait_for_key_release` the key is stored as soon as it is pressed.
+    /// If `key_held_plays_sound` is enabled the sound plays while the key is held.
+    /// Returns `false` while still waiting.
+    pub(super) fn wait_for_key_press(&mut self, x_register_index: usize) -> bool {
+        if self.key_awaiting_release.is_none() {
+            let pressed_key = self
+                .keypad
+                .iter()
+                .position(|&is_key_pressed| is_key_pressed)
+                .map(|key| key as u8);
+            match pressed_key {
+                Some(key) if !self.configuration.wait_for_key_release() => {
+                    self.variable_register[x_register_index] = key;
+                    return true;
+                }
+                _ => self.key_awaiting_release = pressed_key,
+            }
+        }
+
+        match self.key_awaiting_release {
+            Some(key) if !self.keypad[key as usize] => {
+                self.key_awaiting_release = None;
+                self.variable_register[x_register_index] = key;
+                return true;
+            }
+            Some(_) if self.configuration.key_held_plays_sound() => {
+                self.sound_timer = self.sound_timer.max(1);
+            }
+            _ => {}
         }
+
+        self.program_counter = self.program_counter.wrapping_sub(2);
+        false
     }
 
     /// Opcode: Fx15
@@ -289,53 +559,318 @@ impl Interpreter {
     ///
     /// Adds `VX` to `I`. `VF` is not affected.
     pub(super) fn address_register_add_assign(&mut self, x_register_index: usize) {
-        self.address_register += self.variable_register[x_register_index] as u16;
+        self.address_register = self
+            .address_register
+            .wrapping_add(self.variable_register[x_register_index] as u16);
     }
 
     /// Opcode: Fx29
     ///
-    /// Sets `I` to the location of the sprite for the character in `VX`
-    /// Font starts at memory address 0
+    /// Sets `I` to the location of the sprite for the character in `VX`.
+    /// Only the lowest nibble of `VX` is used. Each character is 5 bytes starting at `font_data_start`.
     pub(super) fn address_register_assign_character_address(&mut self, x_register_index: usize) {
-        self.address_register = self.variable_register[x_register_index] as u16 & 0x0F;
+        let character = self.variable_register[x_register_index] as usize & 0x0F;
+        self.address_register = (self.configuration.font_data_start() + character * 5) as u16;
+    }
+
+    /// Opcode: Fx30 (SUPER-CHIP 1.1)
+    ///
+    /// Sets `I` to the location of the 8x10 sprite for the character in `VX`.
+    /// Only the lowest nibble of `VX` is used. Each character is 10 bytes starting at `big_font_data_start`.
+    pub(super) fn address_register_assign_big_character_address(
+        &mut self,
+        x_register_index: usize,
+    ) {
+        let character = self.variable_register[x_register_index] as usize & 0x0F;
+        self.address_register = (self.configuration.big_font_data_start() + character * 10) as u16;
     }
 
     /// Opcode: Fx33
     ///
     /// Stores the binary-coded decimal representation of variable_register[x_register_index], with the hundreds digit in memory at location in `I`, the tens digit at location `I`+1, and the ones digit at location `I`+2
-    pub(super) fn store_binary_coded_decimal_address(&mut self, x_register_index: usize) {
+    pub(super) fn store_binary_coded_decimal_address(
+        &mut self,
+        x_register_index: usize,
+    ) -> Result<(), ExecutionError> {
+        let range = self.memory_range(self.address_register as usize, 3)?;
+        self.record_memory_access(MemoryAccessKind::Write, &range);
+        let address = range.start;
         let mut accumulator = self.variable_register[x_register_index];
 
-        self.memory[self.address_register as usize + 2] = accumulator % 10;
+        self.memory[address + 2] = accumulator % 10;
         accumulator /= 10;
 
-        self.memory[self.address_register as usize + 1] = accumulator % 10;
+        self.memory[address + 1] = accumulator % 10;
         accumulator /= 10;
 
-        self.memory[self.address_register as usize] = accumulator;
+        self.memory[address] = accumulator;
+
+        Ok(())
+    }
+
+    /// Opcode: Fx3A (XO-CHIP)
+    ///
+    /// Sets the audio pattern `pitch` to `VX`
+    pub(super) fn pitch_assign(&mut self, x_register_index: usize) {
+        self.pitch = self.variable_register[x_register_index];
     }
 
     /// Opcode: Fx55
     ///
     /// Stores from `V0` to `VX` (including `VX`) in memory, starting at address `I`.
-    /// The offset from `I` is increased by 1 for each value written, but `I` itself is left unmodified.
-    pub(super) fn store_variable_registers(&mut self, x_register_index: usize) {
-        let address = self.address_register as usize;
+    /// The offset from `I` is increased by 1 for each value written, but `I` itself is left unmodified
+    /// unless `increment_on_store` is enabled.
+    pub(super) fn store_variable_registers(
+        &mut self,
+        x_register_index: usize,
+    ) -> Result<(), ExecutionError> {
+        let range = self.memory_range(self.address_register as usize, x_register_index + 1)?;
+        self.record_memory_access(MemoryAccessKind::Write, &range);
 
-        for offset in 0..=x_register_index {
-            self.memory[address + offset] = self.variable_register[offset];
-        }
+        self.memory[range].copy_from_slice(&self.variable_register[..=x_register_index]);
+        self.increment_address_register_after_store(x_register_index);
+
+        Ok(())
     }
 
     /// Opcode: Fx65
     ///
     /// Fills from `V0` to `VX` (including `VX`) with values from memory, starting at address `I`.
-    /// The offset from `I` is increased by 1 for each value read, but `I` itself is left unmodified.
-    pub(super) fn load_variable_registers(&mut self, x_register_index: usize) {
-        let address = self.address_register as usize;
+    /// The offset from `I` is increased by 1 for each value read, but `I` itself is left unmodified
+    /// unless `increment_on_store` is enabled.
+    pub(super) fn load_variable_registers(
+        &mut self,
+        x_register_index: usize,
+    ) -> Result<(), ExecutionError> {
+        let range = self.memory_range(self.address_register as usize, x_register_index + 1)?;
+        self.record_memory_access(MemoryAccessKind::Read, &range);
+
+        self.variable_register[..=x_register_index].copy_from_slice(&self.memory[range]);
+        self.increment_address_register_after_store(x_register_index);
+
+        Ok(())
+    }
+
+    /// Opcode: Fx75 (SUPER-CHIP 1.0)
+    ///
+    /// Stores from `V0` to `VX` (including `VX`) in the RPL user flags.
+    pub(super) fn store_flag_registers(&mut self, x_register_index: usize) {
+        self.flag_registers[..=x_register_index]
+            .copy_from_slice(&self.variable_register[..=x_register_index]);
+    }
+
+    /// Opcode: Fx85 (SUPER-CHIP 1.0)
+    ///
+    /// Fills from `V0` to `VX` (including `VX`) with values from the RPL user flags.
+    pub(super) fn load_flag_registers(&mut self, x_register_index: usize) {
+        self.variable_register[..=x_register_index]
+            .copy_from_slice(&self.flag_registers[..=x_register_index]);
+    }
 
-        for offset in 0..=x_register_index {
-            self.variable_register[offset] = self.memory[address + offset];
+    fn increment_address_register_after_store(&mut self, x_register_index: usize) {
+        if self.configuration.increment_on_store() {
+            self.address_register = self
+                .address_register
+                .wrapping_add(x_register_index as u16 + 1);
         }
     }
 }
+
+#[cfg(test)]
+fn run_program(builder: crate::interpreter::ConfigurationBuilder, program: &[u8]) -> Interpreter {
+    let mut interpreter = builder.build();
+    interpreter.load_program_from_bytes(program).unwrap();
+    for _ in 0..program.len() / 2 {
+        interpreter.step().unwrap();
+    }
+    interpreter
+}
+#[test]
+fn test_jump_offset_quirk() {
+    // V0 = 0x10, V3 = 0x20, jump 0x300 + offset
+    let program = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x00];
+    let variable = run_program(Interpreter::builder().use_variable_offset(true), &program);
+    let zero = run_program(Interpreter::builder().use_variable_offset(false), &program);
+    assert_eq!(variable.program_counter, 0x320);
+    assert_eq!(zero.program_counter, 0x310);
+}
+#[test]
+fn test_increment_on_store_quirk() {
+    // I = 0x300, store V0..=V2
+    let program = [0xA3, 0x00, 0xF2, 0x55];
+    let incremented = run_program(Interpreter::builder().increment_on_store(true), &program);
+    let unchanged = run_program(Interpreter::builder().increment_on_store(false), &program);
+    assert_eq!(incremented.address_register, 0x303);
+    assert_eq!(unchanged.address_register, 0x300);
+}
+#[test]
+fn test_shift_quirk() {
+    // V1 = 0x01, V2 = 0x80, V1 = V? << 1
+    let program = [0x61, 0x01, 0x62, 0x80, 0x81, 0x2E];
+    let y_register = run_program(Interpreter::builder().shift_uses_y_register(true), &program);
+    let x_register = run_program(
+        Interpreter::builder().shift_uses_y_register(false),
+        &program,
+    );
+    assert_eq!(y_register.variable_register[0x1], 0x00);
+    assert_eq!(y_register.variable_register[0xF], 1);
+    assert_eq!(x_register.variable_register[0x1], 0x02);
+    assert_eq!(x_register.variable_register[0xF], 0);
+}
+#[test]
+fn test_logic_resets_flag_quirk() {
+    // VF = 1, V0 |= V1
+    let program = [0x6F, 0x01, 0x80, 0x11];
+    let reset = run_program(Interpreter::builder().logic_resets_flag(true), &program);
+    let kept = run_program(Interpreter::builder().logic_resets_flag(false), &program);
+    assert_eq!(reset.variable_register[0xF], 0);
+    assert_eq!(kept.variable_register[0xF], 1);
+}
+#[test]
+fn test_clip_sprites_quirk() {
+    // V0 = 62, V1 = 0, I = font "0", draw 5 rows at (62, 0)
+    let program = [0x60, 62, 0x61, 0x00, 0xA0, 0x50, 0xD0, 0x15];
+    let clipped = run_program(Interpreter::builder().clip_sprites(true), &program);
+    let wrapped = run_program(Interpreter::builder().clip_sprites(false), &program);
+    assert!(clipped.display.pixel(63, 0));
+    assert!(!clipped.display.pixel(0, 0));
+    assert!(wrapped.display.pixel(0, 0));
+}
+#[test]
+fn test_assembly_routine_quirk() {
+    let program = [0x02, 0x30];
+    let mut interpreter = Interpreter::builder().use_assembly_routine(true).build();
+    interpreter.load_program_from_bytes(program).unwrap();
+    let expected = ExecutionError::AssemblyRoutine { address: 0x230 };
+    assert_eq!(interpreter.step(), Err(expected));
+
+    let ignored = run_program(Interpreter::builder().use_assembly_routine(false), &program);
+    assert_eq!(ignored.program_counter, 0x202);
+}
+#[test]
+fn test_wait_for_key_release() {
+    use crate::StepOutcome;
+    let mut interpreter = Interpreter::builder().wait_for_key_release(true).build();
+    // V3 = key
+    interpreter.load_program_from_bytes([0xF3, 0x0A]).unwrap();
+    interpreter.keypad_mut()[0x7] = true;
+    assert_eq!(interpreter.step(), Ok(StepOutcome::WaitingForKey));
+    assert!(interpreter.sound_timer > 0);
+    interpreter.keypad_mut()[0x7] = false;
+    assert!(matches!(interpreter.step(), Ok(StepOutcome::Executed(_))));
+    assert_eq!(interpreter.variable_register[0x3], 0x7);
+
+    let mut interpreter = Interpreter::default();
+    interpreter.load_program_from_bytes([0xF3, 0x0A]).unwrap();
+    interpreter.keypad_mut()[0x7] = true;
+    assert!(matches!(interpreter.step(), Ok(StepOutcome::Executed(_))));
+    assert_eq!(interpreter.variable_register[0x3], 0x7);
+}
+#[test]
+fn test_super_chip_high_resolution() {
+    use crate::interpreter::Platform;
+    // high resolution, V0 = 120, I = 0x300, draw 16x16 at (120, 0)
+    let program = [0x00, 0xFF, 0x60, 120, 0xA3, 0x00, 0xD0, 0x10];
+    let builder = crate::interpreter::ConfigurationBuilder::preset(Platform::SuperChip11);
+    let mut interpreter = run_program(builder.clip_sprites(false), &program);
+    assert_eq!(interpreter.take_resolution_change(), Some(Resolution::High));
+    assert_eq!(interpreter.take_resolution_change(), None);
+    assert_eq!(interpreter.display.height(), Resolution::High.height());
+    assert_eq!(interpreter.display.width(), Resolution::High.width());
+
+    // the sprite at 0x300 is zeroed so nothing is drawn, fill it and draw again
+    interpreter.memory[0x300..0x320].fill(0xFF);
+    interpreter.program_counter = 0x206;
+    interpreter.step().unwrap();
+    assert!(interpreter.display.pixel(127, 15));
+    assert!(interpreter.display.pixel(3, 15));
+    assert!(!interpreter.display.pixel(0, 16));
+
+    // scroll down 1 and left 4
+    interpreter.memory[0x208..0x20C].copy_from_slice(&[0x00, 0xC1, 0x00, 0xFC]);
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert!(!interpreter.display.pixel(0, 0));
+    assert!(interpreter.display.pixel(123, 16));
+    assert!(!interpreter.display.pixel(124, 16));
+}
+#[test]
+fn test_super_chip_exit_and_flags() {
+    use crate::{interpreter::Platform, StepOutcome};
+    // V0 = 7, store V0 in flags, V0 = 0, load V0 from flags, exit
+    let program = [0x60, 0x07, 0xF0, 0x75, 0x60, 0x00, 0xF0, 0x85, 0x00, 0xFD];
+    let builder = crate::interpreter::ConfigurationBuilder::preset(Platform::SuperChip10);
+    let mut interpreter = run_program(builder, &program);
+    assert_eq!(interpreter.variable_register[0x0], 0x07);
+    assert_eq!(interpreter.program_counter, 0x20A);
+    assert_eq!(interpreter.step(), Ok(StepOutcome::Exited));
+    assert_eq!(interpreter.program_counter, 0x20A);
+}
+#[test]
+fn test_super_chip_instructions_are_gated() {
+    // high resolution is a machine language routine on chip8
+    let interpreter = run_program(Interpreter::builder(), &[0x00, 0xFF]);
+    assert_eq!(interpreter.display.width(), 64);
+
+    let mut interpreter = Interpreter::builder().build();
+    interpreter.load_program_from_bytes([0xF0, 0x75]).unwrap();
+    let expected = ExecutionError::UnknownOpcode {
+        program_counter: 0x200,
+        opcode: 0xF075,
+    };
+    assert_eq!(interpreter.step(), Err(expected));
+}
+#[test]
+fn test_character_address() {
+    use crate::interpreter::Platform;
+    // V0 = 0xA, I = small A, I = big A
+    let program = [0x60, 0x0A, 0xF0, 0x29];
+    let interpreter = run_program(Interpreter::builder(), &program);
+    assert_eq!(interpreter.address_register, 0x50 + 0xA * 5);
+
+    let program = [0x60, 0x0A, 0xF0, 0x30];
+    let builder = crate::interpreter::ConfigurationBuilder::preset(Platform::SuperChip11);
+    let interpreter = run_program(builder, &program);
+    assert_eq!(interpreter.address_register, 0xA0 + 0xA * 10);
+}
+#[test]
+fn test_xo_chip_planes() {
+    use crate::interpreter::Platform;
+    // select both planes, I = long 0x0300, draw 1 row per plane at (0, 0)
+    let program = [0xF3, 0x01, 0xF0, 0x00, 0x03, 0x00, 0xD0, 0x01];
+    let mut interpreter =
+        crate::interpreter::ConfigurationBuilder::preset(Platform::XoChip).build();
+    interpreter.load_program_from_bytes(program).unwrap();
+    interpreter.memory[0x300..0x302].copy_from_slice(&[0b11000000, 0b10000000]);
+    for _ in 0..3 {
+        interpreter.step().unwrap();
+    }
+    assert_eq!(interpreter.address_register, 0x300);
+    assert_eq!(interpreter.display.color_index(0, 0), 0b11);
+    assert_eq!(interpreter.display.color_index(1, 0), 0b01);
+
+    // select plane 2, clear
+    interpreter.memory[0x208..0x20C].copy_from_slice(&[0xF2, 0x01, 0x00, 0xE0]);
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.display.color_index(0, 0), 0b01);
+}
+#[test]
+fn test_xo_chip_register_range_and_skip() {
+    use crate::interpreter::Platform;
+    // V1 = 1, V2 = 2, I = 0x300, save V2..V1, skip if V1 == 1 over a long load, load V1..V2
+    let program = [
+        0x61, 0x01, 0x62, 0x02, 0xA3, 0x00, 0x52, 0x12, 0x31, 0x01, 0xF0, 0x00, 0x01, 0x23, 0x51,
+        0x23,
+    ];
+    let mut interpreter =
+        crate::interpreter::ConfigurationBuilder::preset(Platform::XoChip).build();
+    interpreter.load_program_from_bytes(program).unwrap();
+    for _ in 0..6 {
+        interpreter.step().unwrap();
+    }
+    assert_eq!(&interpreter.memory[0x300..0x302], &[0x02, 0x01]);
+    assert_eq!(interpreter.address_register, 0x300);
+    assert_eq!(interpreter.variable_register[0x1], 0x02);
+    assert_eq!(interpreter.variable_register[0x2], 0x01);
+}
@@ -0,0 +1,266 @@
+use crate::interpreter::save_state::{Reader, Writer};
+use crate::interpreter::{
+    program_hash, Configuration, ConfigurationBuilder, ExecutionError, Interpreter, SaveStateError,
+};
+
+/// Identifies the bytes of a [Movie].
+const MOVIE_MAGIC: [u8; 4] = *b"CH8M";
+/// Incremented whenever the encoding of a [Movie] changes.
+pub const MOVIE_VERSION: u16 = 2;
+
+/// The keypad of every frame of a session, starting from a freshly loaded program.
+///
+/// Together with the program hash and the [Configuration] (which holds the random seed)
+/// a movie reproduces a session bit for bit when it is replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    program_hash: u64,
+    configuration: Configuration,
+
+    /// One bit per key for every frame. Key `0x0` is the least significant bit.
+    frames: Vec<u16>,
+}
+
+// initialization
+impl Movie {
+    /// Starts a recording of `interpreter`, which should have just loaded its program.
+    pub fn new(interpreter: &Interpreter) -> Self {
+        Self {
+            program_hash: interpreter.program_hash(),
+            configuration: *interpreter.configuration(),
+            frames: Vec::new(),
+        }
+    }
+}
+
+// accessors
+impl Movie {
+    /// The [program_hash] of the recorded program.
+    pub const fn program_hash(&self) -> u64 {
+        self.program_hash
+    }
+
+    pub const fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    pub fn random_seed(&self) -> u64 {
+        self.configuration.random_seed()
+    }
+
+    /// The number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the keypad of the frame at `index`.
+    pub fn keypad(&self, index: usize) -> Option<[bool; 16]> {
+        let keys = self.frames.get(index)?;
+        Some(std::array::from_fn(|key| keys & (1 << key) != 0))
+    }
+}
+
+// mutators
+impl Movie {
+    /// Records the keypad of the next frame. Call this once per frame before [Interpreter::run_frame].
+    pub fn record_frame(&mut self, keypad: &[bool; 16]) {
+        let keys = keypad
+            .iter()
+            .enumerate()
+            .fold(0, |keys, (key, &is_pressed)| {
+                keys | (is_pressed as u16) << key
+            });
+        self.frames.push(keys);
+    }
+
+    /// Builds an [Interpreter] with the recorded configuration and loads `program_data` into it.
+    ///
+    /// Returns an error if `program_data` is not the recorded program.
+    pub fn replay(&self, program_data: impl AsRef<[u8]>) -> Result<Replay<'_>, MovieError> {
+        let program_data = program_data.as_ref();
+        let found = program_hash(program_data);
+        if found != self.program_hash {
+            return Err(MovieError::ProgramMismatch {
+                expected: self.program_hash,
+                found,
+            });
+        }
+
+        let mut interpreter = ConfigurationBuilder::from(self.configuration).build();
+        interpreter
+            .load_program_from_bytes(program_data)
+            .map_err(MovieError::Load)?;
+        Ok(Replay {
+            movie: self,
+            interpreter,
+            frame: 0,
+        })
+    }
+}
+
+// encoding
+impl Movie {
+    /// Encodes the movie as little-endian bytes.
+    ///
+    /// The header holds [MOVIE_VERSION], the program hash and the configuration.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes(&MOVIE_MAGIC);
+        writer.u16(MOVIE_VERSION);
+        writer.u64(self.program_hash);
+        self.configuration.encode(&mut writer);
+        writer.usize(self.frames.len());
+        self.frames.iter().for_each(|&keys| writer.u16(keys));
+        writer.into_bytes()
+    }
+
+    /// Decodes bytes created by [Movie::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = Reader::new(bytes);
+        if reader.array::<4>()? != MOVIE_MAGIC {
+            return Err(MovieError::UnknownFormat);
+        }
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let program_hash = reader.u64()?;
+        let configuration = Configuration::decode(&mut reader)?;
+        let frame_count = reader.usize()?;
+        let frames = (0..frame_count)
+            .map(|_| reader.u16())
+            .collect::<Result<_, _>>()?;
+        reader.finish()?;
+
+        Ok(Self {
+            program_hash,
+            configuration,
+            frames,
+        })
+    }
+}
+
+/// Plays back a [Movie] one frame at a time. Created by [Movie::replay].
+#[derive(Debug)]
+pub struct Replay<'a> {
+    movie: &'a Movie,
+    interpreter: Interpreter,
+
+    /// The index of the next frame to play.
+    frame: usize,
+}
+
+// accessors
+impl Replay<'_> {
+    pub const fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// The number of frames played so far.
+    pub const fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.len()
+    }
+}
+
+// mutators
+impl Replay<'_> {
+    /// Presses the recorded keys and runs one [Interpreter::run_frame].
+    /// Returns `Ok(false)` once every frame has been played.
+    pub fn run_frame(&mut self) -> Result<bool, ExecutionError> {
+        let Some(keypad) = self.movie.keypad(self.frame) else {
+            return Ok(false);
+        };
+        *self.interpreter.keypad_mut() = keypad;
+        self.interpreter.run_frame()?;
+        self.frame += 1;
+        Ok(true)
+    }
+
+    /// Plays every remaining frame.
+    pub fn run_to_end(&mut self) -> Result<(), ExecutionError> {
+        while self.run_frame()? {}
+        Ok(())
+    }
+
+    pub fn into_interpreter(self) -> Interpreter {
+        self.interpreter
+    }
+}
+
+/// The reasons a [Movie] can not be decoded or replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MovieError {
+    /// The bytes do not start with the movie header.
+    UnknownFormat,
+
+    /// The bytes were encoded by a different [MOVIE_VERSION].
+    UnsupportedVersion(u16),
+
+    /// The configuration or frames could not be decoded.
+    InvalidData(SaveStateError),
+
+    /// The program given to [Movie::replay] is not the recorded program.
+    ProgramMismatch { expected: u64, found: u64 },
+
+    /// The program given to [Movie::replay] does not fit in the memory of the recorded configuration.
+    Load(ExecutionError),
+}
+impl From<SaveStateError> for MovieError {
+    fn from(error: SaveStateError) -> Self {
+        Self::InvalidData(error)
+    }
+}
+impl std::fmt::Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "not a chip8 movie"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported movie version {version}"),
+            Self::InvalidData(error) => write!(f, "invalid movie: {error}"),
+            Self::ProgramMismatch { expected, found } => write!(
+                f,
+                "movie was recorded with program {expected:#018X} but program {found:#018X} was given"
+            ),
+            Self::Load(error) => write!(f, "could not load the program: {error}"),
+        }
+    }
+}
+impl std::error::Error for MovieError {}
+
+#[test]
+fn test_movie_replay_is_identical() {
+    let configuration = Interpreter::builder()
+        .instructions_per_frame(4)
+        .random_seed(42);
+    // 0x200: skip if key V0 is pressed, jump 0x200, V1 = random, draw V1 V1, jump 0x200
+    let program = [0xE0, 0x9E, 0x12, 0x00, 0xC1, 0x3F, 0xD1, 0x15, 0x12, 0x00];
+    let mut interpreter = configuration.build();
+    interpreter.load_program_from_bytes(program).unwrap();
+
+    let mut movie = Movie::new(&interpreter);
+    for frame in 0..20 {
+        interpreter.keypad_mut()[0x0] = frame % 3 == 0;
+        movie.record_frame(interpreter.keypad());
+        interpreter.run_frame().unwrap();
+    }
+
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.random_seed(), 42);
+    let mut replay = movie.replay(program).unwrap();
+    replay.run_to_end().unwrap();
+    assert_eq!(replay.frame(), 20);
+    assert_eq!(replay.interpreter().save_state(), interpreter.save_state());
+
+    let wrong_program = movie.replay([0x12, 0x00]);
+    assert!(matches!(
+        wrong_program,
+        Err(MovieError::ProgramMismatch { .. })
+    ));
+}
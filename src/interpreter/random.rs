@@ -0,0 +1,126 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+/// The source of the random numbers used by `Cxkk`.
+pub trait RandomSource: std::fmt::Debug + Send {
+    /// Returns the next random byte.
+    fn next_byte(&mut self) -> u8;
+
+    /// The state captured by a [SaveState](super::SaveState).
+    /// Sources that can not be restored return `0`.
+    fn state(&self) -> u64 {
+        0
+    }
+
+    /// Restores a state returned by [RandomSource::state].
+    fn set_state(&mut self, _state: u64) {}
+}
+
+/// A fast xorshift generator. This is the default [RandomSource], seeded with
+/// [Configuration::random_seed](super::Configuration::random_seed).
+///
+/// A seed of zero only generates zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct XorShiftRandomSource {
+    state: u64,
+}
+impl XorShiftRandomSource {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+impl RandomSource for XorShiftRandomSource {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state & 0xFF) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+/// Random numbers from a [rand] generator. Its state is not part of save states.
+#[derive(Debug, Clone)]
+pub struct RngRandomSource<R = StdRng> {
+    rng: R,
+}
+impl RngRandomSource {
+    /// A generator seeded by the operating system. Every run is different.
+    pub fn from_entropy() -> Self {
+        Self::new(StdRng::from_entropy())
+    }
+}
+impl<R: RngCore> RngRandomSource<R> {
+    pub const fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+impl<R: RngCore + std::fmt::Debug + Send> RandomSource for RngRandomSource<R> {
+    fn next_byte(&mut self) -> u8 {
+        (self.rng.next_u32() & 0xFF) as u8
+    }
+}
+
+/// Returns a fixed sequence of bytes, starting over after the last one.
+/// Makes programs that roll dice testable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptedRandomSource {
+    bytes: Vec<u8>,
+
+    /// The index of the next byte.
+    position: usize,
+}
+impl ScriptedRandomSource {
+    /// # Panics
+    /// If `bytes` is empty
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        assert!(
+            !bytes.is_empty(),
+            "a scripted random source needs at least one byte"
+        );
+        Self { bytes, position: 0 }
+    }
+}
+impl RandomSource for ScriptedRandomSource {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.position];
+        self.position = (self.position + 1) % self.bytes.len();
+        byte
+    }
+
+    fn state(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.position = state as usize % self.bytes.len();
+    }
+}
+
+#[test]
+fn test_scripted_random_source() {
+    let mut random_source = ScriptedRandomSource::new([1, 2, 3]);
+    assert_eq!(random_source.next_byte(), 1);
+    let state = random_source.state();
+    assert_eq!(random_source.next_byte(), 2);
+    assert_eq!(random_source.next_byte(), 3);
+    assert_eq!(random_source.next_byte(), 1);
+    random_source.set_state(state);
+    assert_eq!(random_source.next_byte(), 2);
+}
+#[test]
+fn test_xor_shift_random_source_is_seeded() {
+    let bytes = |seed| {
+        let mut random_source = XorShiftRandomSource::new(seed);
+        [(); 8].map(|_| random_source.next_byte())
+    };
+    assert_eq!(bytes(1), bytes(1));
+    assert_ne!(bytes(1), bytes(2));
+}
@@ -0,0 +1,238 @@
+use crate::interpreter::{Interpreter, SaveState, SaveStateError};
+use std::collections::VecDeque;
+
+/// Runs of unchanged bytes shorter than this are copied into a delta instead of starting a new run.
+const MINIMUM_SKIP_LENGTH: usize = 8;
+
+/// How much history a [RewindBuffer] keeps before the oldest snapshots are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RewindBudget {
+    /// Keeps at most this many snapshots.
+    Frames(usize),
+    /// Keeps snapshots while they fit in this many bytes. The newest snapshot is always kept.
+    Bytes(usize),
+}
+
+/// A ring buffer of [SaveState] snapshots for stepping an [Interpreter] backwards in time.
+///
+/// Only the newest snapshot is stored whole.
+/// Every older snapshot is stored as the bytes that differ from the snapshot after it,
+/// so the mostly static memory costs almost nothing per frame.
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    budget: RewindBudget,
+
+    /// The encoded newest snapshot.
+    newest: Option<Vec<u8>>,
+
+    /// `deltas[i]` turns snapshot `i + 1` into snapshot `i`. The oldest snapshot is first.
+    deltas: VecDeque<Vec<u8>>,
+
+    /// The size of `newest` plus every delta.
+    size_in_bytes: usize,
+}
+
+// initialization
+impl RewindBuffer {
+    pub fn new(budget: RewindBudget) -> Self {
+        Self {
+            budget,
+            newest: None,
+            deltas: VecDeque::new(),
+            size_in_bytes: 0,
+        }
+    }
+}
+
+// accessors
+impl RewindBuffer {
+    pub const fn budget(&self) -> RewindBudget {
+        self.budget
+    }
+
+    /// The number of snapshots that can be rewound.
+    pub fn len(&self) -> usize {
+        self.newest.iter().count() + self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// The memory used by the stored snapshots.
+    pub const fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
+
+// mutators
+impl RewindBuffer {
+    /// Stores a snapshot of `interpreter`. Call this once per frame before [Interpreter::run_frame].
+    pub fn push(&mut self, interpreter: &Interpreter) {
+        let snapshot = interpreter.save_state().to_bytes();
+        self.size_in_bytes += snapshot.len();
+        if let Some(previous) = self.newest.replace(snapshot) {
+            let delta = encode_delta(self.newest.as_deref().unwrap(), &previous);
+            self.size_in_bytes += delta.len();
+            self.size_in_bytes -= previous.len();
+            self.deltas.push_back(delta);
+        }
+
+        while self.is_over_budget() {
+            let Some(oldest) = self.deltas.pop_front() else {
+                break;
+            };
+            self.size_in_bytes -= oldest.len();
+        }
+    }
+
+    /// Restores the newest snapshot into `interpreter` and removes it from the buffer.
+    ///
+    /// Returns `Ok(false)` when there is nothing left to rewind.
+    /// Returns an error if the snapshot was taken while a different program was loaded.
+    pub fn rewind(&mut self, interpreter: &mut Interpreter) -> Result<bool, SaveStateError> {
+        let Some(snapshot) = self.pop() else {
+            return Ok(false);
+        };
+        let state = SaveState::from_bytes(&snapshot)?;
+        interpreter.load_state(&state)?;
+        Ok(true)
+    }
+
+    /// Removes every snapshot.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.size_in_bytes = 0;
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        self.size_in_bytes -= newest.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            let previous = apply_delta(&newest, &delta);
+            self.size_in_bytes -= delta.len();
+            self.size_in_bytes += previous.len();
+            self.newest = Some(previous);
+        }
+        Some(newest)
+    }
+
+    fn is_over_budget(&self) -> bool {
+        match self.budget {
+            RewindBudget::Frames(frames) => self.len() > frames.max(1),
+            RewindBudget::Bytes(bytes) => self.size_in_bytes > bytes,
+        }
+    }
+}
+
+/// Encodes the bytes that turn `base` into `target`.
+///
+/// The delta starts with the length of `target` followed by runs of
+/// `[unchanged length: u32][changed length: u32][changed bytes]`.
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = (target.len() as u32).to_le_bytes().to_vec();
+    let is_changed = |i: usize| base.get(i) != target.get(i);
+
+    let mut run_end = 0;
+    while run_end < target.len() {
+        let Some(run_start) = (run_end..target.len()).find(|&i| is_changed(i)) else {
+            break;
+        };
+        // extend the run over short stretches of unchanged bytes
+        let mut end = run_start + 1;
+        while end < target.len() {
+            if is_changed(end) {
+                end += 1;
+                continue;
+            }
+            let next_change =
+                (end..target.len().min(end + MINIMUM_SKIP_LENGTH)).find(|&i| is_changed(i));
+            match next_change {
+                Some(i) => end = i + 1,
+                None => break,
+            }
+        }
+
+        delta.extend_from_slice(&((run_start - run_end) as u32).to_le_bytes());
+        delta.extend_from_slice(&((end - run_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&target[run_start..end]);
+        run_end = end;
+    }
+    delta
+}
+
+/// Rebuilds the `target` given to [encode_delta] from its `base`.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+
+    let target_length = read_u32(delta);
+    let mut target = base.to_vec();
+    target.resize(target_length, 0);
+
+    let mut delta = &delta[4..];
+    let mut position = 0;
+    while !delta.is_empty() {
+        position += read_u32(delta);
+        let changed_length = read_u32(&delta[4..]);
+        let changed = &delta[8..8 + changed_length];
+        target[position..position + changed_length].copy_from_slice(changed);
+        position += changed_length;
+        delta = &delta[8 + changed_length..];
+    }
+    target
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let base = [
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+    ];
+    let mut target = base.to_vec();
+    target[2] = 0;
+    target[5] = 0;
+    target[19] = 0;
+    target.extend_from_slice(&[21, 22]);
+    assert_eq!(apply_delta(&base, &encode_delta(&base, &target)), target);
+    assert_eq!(apply_delta(&target, &encode_delta(&target, &base)), base);
+    assert_eq!(encode_delta(&base, &base).len(), 4);
+}
+#[test]
+fn test_rewind_restores_frames() {
+    let mut interpreter = Interpreter::builder().instructions_per_frame(2).build();
+    // V0 = 60, delay timer = V0, 0x204: V1 += 1, draw V1 V1, jump 0x204
+    let program = [0x60, 0x3C, 0xF0, 0x15, 0x71, 0x01, 0xD1, 0x11, 0x12, 0x04];
+    interpreter.load_program_from_bytes(program).unwrap();
+
+    let mut rewind_buffer = RewindBuffer::new(RewindBudget::Frames(3));
+    let mut states = Vec::new();
+    for _ in 0..5 {
+        rewind_buffer.push(&interpreter);
+        states.push(interpreter.save_state());
+        interpreter.run_frame().unwrap();
+    }
+    assert_eq!(rewind_buffer.len(), 3);
+
+    for state in states.iter().rev().take(3) {
+        assert_eq!(rewind_buffer.rewind(&mut interpreter), Ok(true));
+        assert_eq!(&interpreter.save_state(), state);
+    }
+    assert_eq!(rewind_buffer.rewind(&mut interpreter), Ok(false));
+    assert_eq!(rewind_buffer.size_in_bytes(), 0);
+}
+#[test]
+fn test_rewind_byte_budget() {
+    let mut interpreter = Interpreter::default();
+    interpreter
+        .load_program_from_bytes([0x70, 0x01, 0x12, 0x00])
+        .unwrap();
+
+    let snapshot_size = interpreter.save_state().to_bytes().len();
+    let mut rewind_buffer = RewindBuffer::new(RewindBudget::Bytes(snapshot_size + 100));
+    for _ in 0..100 {
+        rewind_buffer.push(&interpreter);
+        interpreter.run_frame().unwrap();
+    }
+    assert!(rewind_buffer.size_in_bytes() <= snapshot_size + 100);
+    assert!(rewind_buffer.len() > 1);
+}
@@ -0,0 +1,361 @@
+use crate::interpreter::{Configuration, Display, Resolution};
+
+/// Identifies the bytes of a [SaveState].
+const SAVE_STATE_MAGIC: [u8; 4] = *b"CH8S";
+/// Incremented whenever the encoding of a [SaveState] changes.
+pub const SAVE_STATE_VERSION: u16 = 3;
+
+/// A snapshot of everything a program can observe in an [Interpreter](super::Interpreter).
+///
+/// Created by [Interpreter::save_state](super::Interpreter::save_state)
+/// and restored by [Interpreter::load_state](super::Interpreter::load_state).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    pub(super) program_hash: u64,
+    pub(super) configuration: Configuration,
+    pub(super) memory: Box<[u8]>,
+    pub(super) program_counter: u16,
+    pub(super) address_register: u16,
+    pub(super) variable_register: [u8; 16],
+    pub(super) call_stack: [u16; 16],
+    pub(super) call_stack_index: usize,
+    pub(super) flag_registers: [u8; 16],
+    pub(super) delay_timer: u8,
+    pub(super) sound_timer: u8,
+    pub(super) key_awaiting_release: Option<u8>,
+    pub(super) vertical_blank: bool,
+    pub(super) random_state: u64,
+    pub(super) display: Display,
+    pub(super) selected_planes: u8,
+    pub(super) audio_pattern: [u8; 16],
+    pub(super) pitch: u8,
+    pub(super) resolution: Resolution,
+    pub(super) exited: bool,
+    pub(super) keypad: [bool; 16],
+}
+
+// accessors
+impl SaveState {
+    /// The [program_hash] of the program that was loaded when the state was saved.
+    pub const fn program_hash(&self) -> u64 {
+        self.program_hash
+    }
+
+    pub const fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    pub const fn display(&self) -> &Display {
+        &self.display
+    }
+}
+
+// encoding
+impl SaveState {
+    /// Encodes the state as little-endian bytes.
+    ///
+    /// The header holds [SAVE_STATE_VERSION], the program hash and the configuration.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes(&SAVE_STATE_MAGIC);
+        writer.u16(SAVE_STATE_VERSION);
+        writer.u64(self.program_hash);
+        self.configuration.encode(&mut writer);
+
+        writer.usize(self.memory.len());
+        writer.bytes(&self.memory);
+        writer.u16(self.program_counter);
+        writer.u16(self.address_register);
+        writer.bytes(&self.variable_register);
+        self.call_stack
+            .iter()
+            .for_each(|&address| writer.u16(address));
+        writer.usize(self.call_stack_index);
+        writer.bytes(&self.flag_registers);
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.bool(self.key_awaiting_release.is_some());
+        writer.u8(self.key_awaiting_release.unwrap_or_default());
+        writer.bool(self.vertical_blank);
+        writer.u64(self.random_state);
+        writer.display(&self.display);
+        writer.u8(self.selected_planes);
+        writer.bytes(&self.audio_pattern);
+        writer.u8(self.pitch);
+        writer.resolution(self.resolution);
+        writer.bool(self.exited);
+        self.keypad.iter().for_each(|&key| writer.bool(key));
+        writer.into_bytes()
+    }
+
+    /// Decodes bytes created by [SaveState::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader::new(bytes);
+        if reader.array::<4>()? != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::UnknownFormat);
+        }
+        let version = reader.u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let program_hash = reader.u64()?;
+        let configuration = Configuration::decode(&mut reader)?;
+
+        let memory_size = reader.usize()?;
+        if memory_size != configuration.memory_size() {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let memory = reader.bytes(memory_size)?.into();
+        let program_counter = reader.u16()?;
+        let address_register = reader.u16()?;
+        let variable_register = reader.array()?;
+        let mut call_stack = [0; 16];
+        for address in &mut call_stack {
+            *address = reader.u16()?;
+        }
+        let call_stack_index = reader.usize()?;
+        if call_stack_index > call_stack.len() {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let flag_registers = reader.array()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let is_awaiting_release = reader.bool()?;
+        let key_awaiting_release = reader.u8()?;
+        if is_awaiting_release && key_awaiting_release >= 16 {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let key_awaiting_release = is_awaiting_release.then_some(key_awaiting_release);
+        let vertical_blank = reader.bool()?;
+        let random_state = reader.u64()?;
+        let display = reader.display()?;
+        let selected_planes = reader.u8()?;
+        let audio_pattern = reader.array()?;
+        let pitch = reader.u8()?;
+        let resolution = reader.resolution()?;
+        if display.width() != resolution.width()
+            || display.height() != resolution.height()
+            || display.plane_count() != configuration.display_plane_count()
+            || selected_planes & !display.all_planes() != 0
+        {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let exited = reader.bool()?;
+        let mut keypad = [false; 16];
+        for key in &mut keypad {
+            *key = reader.bool()?;
+        }
+        reader.finish()?;
+
+        Ok(Self {
+            program_hash,
+            configuration,
+            memory,
+            program_counter,
+            address_register,
+            variable_register,
+            call_stack,
+            call_stack_index,
+            flag_registers,
+            delay_timer,
+            sound_timer,
+            key_awaiting_release,
+            vertical_blank,
+            random_state,
+            display,
+            selected_planes,
+            audio_pattern,
+            pitch,
+            resolution,
+            exited,
+            keypad,
+        })
+    }
+}
+
+/// The reasons a [SaveState] can not be decoded or loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveStateError {
+    /// The bytes do not start with the save state header.
+    UnknownFormat,
+
+    /// The bytes were encoded by a different [SAVE_STATE_VERSION].
+    UnsupportedVersion(u16),
+
+    /// The bytes ended before the whole state was decoded.
+    UnexpectedEnd,
+
+    /// A decoded value is out of range or inconsistent with the configuration.
+    InvalidValue,
+
+    /// The state was saved while a different program was loaded.
+    ProgramMismatch { expected: u64, found: u64 },
+}
+impl std::fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "not a chip8 save state"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {version}")
+            }
+            Self::UnexpectedEnd => write!(f, "save state ended unexpectedly"),
+            Self::InvalidValue => write!(f, "save state contains an invalid value"),
+            Self::ProgramMismatch { expected, found } => write!(
+                f,
+                "save state is for program {found:#018X} but program {expected:#018X} is loaded"
+            ),
+        }
+    }
+}
+impl std::error::Error for SaveStateError {}
+
+/// Hashes a program with 64 bit FNV-1a. Used to tell which program a [SaveState] belongs to.
+pub const fn program_hash(program_data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+    const PRIME: u64 = 0x100000001B3;
+
+    let mut hash = OFFSET_BASIS;
+    let mut i = 0;
+    while i < program_data.len() {
+        hash ^= program_data[i] as u64;
+        hash = hash.wrapping_mul(PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// Appends little-endian values to a buffer.
+#[derive(Debug, Default)]
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+impl Writer {
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    pub(crate) fn resolution(&mut self, resolution: Resolution) {
+        self.u8(match resolution {
+            Resolution::Low => 0,
+            Resolution::High => 1,
+        });
+    }
+
+    pub(crate) fn display(&mut self, display: &Display) {
+        self.usize(display.width());
+        self.usize(display.height());
+        self.usize(display.plane_count());
+        self.bytes(display.color_indexes());
+    }
+}
+
+/// Reads the little-endian values appended by a [Writer].
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> Reader<'a> {
+    pub(crate) const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Returns an error if any bytes were not read.
+    pub(crate) fn finish(self) -> Result<(), SaveStateError> {
+        match self.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(SaveStateError::InvalidValue),
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let (bytes, rest) = self
+            .bytes
+            .split_at_checked(length)
+            .ok_or(SaveStateError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::InvalidValue),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, SaveStateError> {
+        usize::try_from(self.u64()?).map_err(|_| SaveStateError::InvalidValue)
+    }
+
+    pub(crate) fn resolution(&mut self) -> Result<Resolution, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(Resolution::Low),
+            1 => Ok(Resolution::High),
+            _ => Err(SaveStateError::InvalidValue),
+        }
+    }
+
+    pub(crate) fn display(&mut self) -> Result<Display, SaveStateError> {
+        let width = self.usize()?;
+        let height = self.usize()?;
+        let plane_count = self.usize()?;
+        if !(1..=Display::MAX_PLANE_COUNT).contains(&plane_count) {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let length = width
+            .checked_mul(height)
+            .ok_or(SaveStateError::InvalidValue)?;
+        let color_indexes = self.bytes(length)?;
+
+        let mut display = Display::new(width, height, plane_count);
+        display.color_indexes_mut().copy_from_slice(color_indexes);
+        Ok(display)
+    }
+}
+
+#[test]
+fn test_program_hash() {
+    assert_eq!(program_hash(&[]), 0xCBF29CE484222325);
+    assert_eq!(program_hash(b"a"), 0xAF63DC4C8601EC8C);
+}
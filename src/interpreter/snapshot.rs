@@ -0,0 +1,125 @@
+use crate::interpreter::Display;
+use std::path::Path;
+
+/// When set [check_display_snapshot] overwrites snapshots with the current display instead of comparing.
+pub const BLESS_VARIABLE: &str = "CHIP8_BLESS";
+
+// snapshots
+impl Display {
+    /// Returns the display as text for golden files.
+    ///
+    /// The first line holds the size and number of planes. Each following line is a row of pixels:
+    /// `.` is unlit, `#` is color index 1 and larger color indexes are hexadecimal digits.
+    /// ```text
+    /// chip8 display 4x2 planes 1
+    /// .#..
+    /// ##.#
+    /// ```
+    pub fn to_snapshot(&self) -> String {
+        let mut snapshot = format!(
+            "chip8 display {}x{} planes {}\n",
+            self.width(),
+            self.height(),
+            self.plane_count()
+        );
+        for row in self.rows() {
+            snapshot.extend(row.iter().map(|&color_index| {
+                match color_index {
+                    0 => '.',
+                    1 => '#',
+                    _ => char::from_digit(color_index as u32, 16)
+                        .unwrap()
+                        .to_ascii_uppercase(),
+                }
+            }));
+            snapshot.push('\n');
+        }
+        snapshot
+    }
+}
+
+/// Compares `display` with the snapshot at `path`. Used by `assert_display_matches!`.
+///
+/// On a mismatch the current display is written to `<path>.new` and the error holds a side by side diff.
+/// When [BLESS_VARIABLE] is set the snapshot is overwritten instead.
+pub fn check_display_snapshot(display: &Display, path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let new_path = path.with_extension(match path.extension() {
+        Some(extension) => format!("{}.new", extension.to_string_lossy()),
+        None => "new".to_owned(),
+    });
+    let actual = display.to_snapshot();
+
+    let write = |path: &Path| {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, &actual)
+            .map_err(|e| format!("could not write {}: {e}", path.display()))
+    };
+
+    if std::env::var_os(BLESS_VARIABLE).is_some() {
+        let _ = std::fs::remove_file(&new_path);
+        return write(path);
+    }
+
+    let Ok(expected) = std::fs::read_to_string(path) else {
+        write(&new_path)?;
+        return Err(format!(
+            "the snapshot {} is missing. The display was written to {}. Set {BLESS_VARIABLE}=1 to accept it",
+            path.display(),
+            new_path.display()
+        ));
+    };
+    if expected == actual {
+        let _ = std::fs::remove_file(&new_path);
+        return Ok(());
+    }
+
+    write(&new_path)?;
+    Err(format!(
+        "the display does not match {}. The display was written to {}. Set {BLESS_VARIABLE}=1 to accept it\n{}",
+        path.display(),
+        new_path.display(),
+        side_by_side_diff(&expected, &actual)
+    ))
+}
+
+/// Lines up two snapshots next to each other. Rows that differ are marked with `<`.
+fn side_by_side_diff(expected: &str, actual: &str) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let width = expected_lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("expected".len());
+
+    let mut diff = format!("{:width$} | actual\n", "expected");
+    for i in 0..expected_lines.len().max(actual_lines.len()) {
+        let expected_line = expected_lines.get(i).copied().unwrap_or_default();
+        let actual_line = actual_lines.get(i).copied().unwrap_or_default();
+        let marker = if expected_line == actual_line {
+            ""
+        } else {
+            " <"
+        };
+        diff.push_str(&format!("{expected_line:width$} | {actual_line}{marker}\n"));
+    }
+    diff
+}
+
+#[test]
+fn test_to_snapshot() {
+    let mut display = Display::new(4, 2, 2);
+    display.toggle_pixel(1, 0, 0b01);
+    display.toggle_pixel(3, 1, 0b11);
+    let expected = "chip8 display 4x2 planes 2\n.#..\n...3\n";
+    assert_eq!(display.to_snapshot(), expected);
+}
+#[test]
+fn test_side_by_side_diff() {
+    let diff = side_by_side_diff("a\nb\n", "a\nc\n");
+    assert_eq!(diff, "expected | actual\na        | a\nb        | c <\n");
+}
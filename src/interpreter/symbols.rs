@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+/// An error in the text given to [SymbolTable::parse]. Lines start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+impl std::fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+impl std::error::Error for SymbolError {}
+
+/// Labels and source lines of a program, used to describe addresses as `main_loop+0x4 (game.8o:37)`.
+///
+/// Created by [Assembly::symbol_table](crate::asm::Assembly::symbol_table) or loaded from text
+/// with one symbol per line. Empty lines and lines starting with `#` are ignored.
+/// ```text
+/// label 0x200 main_loop
+/// line 0x204 37 game.8o
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    labels: BTreeMap<u16, String>,
+    /// The source file and line of an address. Files are indexes into `files`.
+    lines: BTreeMap<u16, (usize, usize)>,
+    files: Vec<String>,
+}
+
+// initialization
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+
+    /// Reads the text written by [SymbolTable::to_text].
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbol_table = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| SymbolError {
+                line: index + 1,
+                message: message.to_owned(),
+            };
+
+            let mut fields = line.splitn(4, char::is_whitespace);
+            let kind = fields.next().unwrap_or_default();
+            let address = fields
+                .next()
+                .and_then(|address| address.strip_prefix("0x"))
+                .and_then(|address| u16::from_str_radix(address, 16).ok())
+                .ok_or_else(|| error("expected a hexadecimal address like 0x200"))?;
+            match kind {
+                "label" => {
+                    let name = fields
+                        .next()
+                        .ok_or_else(|| error("expected a label name"))?;
+                    symbol_table.insert_label(address, name);
+                }
+                "line" => {
+                    let source_line = fields
+                        .next()
+                        .and_then(|line| line.parse().ok())
+                        .ok_or_else(|| error("expected a line number"))?;
+                    let file = fields.next().ok_or_else(|| error("expected a file name"))?;
+                    symbol_table.insert_line(address, file, source_line);
+                }
+                _ => return Err(error("expected `label` or `line`")),
+            }
+        }
+        Ok(symbol_table)
+    }
+}
+
+// accessors
+impl SymbolTable {
+    /// Returns the label defined at exactly `address`.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Returns the address of the label `name`.
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.labels
+            .iter()
+            .find(|(_, label)| *label == name)
+            .map(|(&address, _)| address)
+    }
+
+    /// Returns the closest label at or before `address` and the distance to it.
+    pub fn nearest_label(&self, address: u16) -> Option<(&str, u16)> {
+        let (&label_address, name) = self.labels.range(..=address).next_back()?;
+        Some((name, address - label_address))
+    }
+
+    /// Returns the source file and line of the statement at or before `address`.
+    pub fn location(&self, address: u16) -> Option<(&str, usize)> {
+        let (_, &(file, line)) = self.lines.range(..=address).next_back()?;
+        Some((&self.files[file], line))
+    }
+
+    /// Returns the first address of the source `line` in `file`.
+    pub fn address_of_line(&self, file: &str, line: usize) -> Option<u16> {
+        let file = self.files.iter().position(|name| name == file)?;
+        self.lines
+            .iter()
+            .find(|(_, &location)| location == (file, line))
+            .map(|(&address, _)| address)
+    }
+
+    /// Returns `address` as `label+0xOFFSET (file:line)`, leaving out the parts that are unknown.
+    pub fn describe(&self, address: u16) -> String {
+        let mut description = match self.nearest_label(address) {
+            Some((name, 0)) => name.to_owned(),
+            Some((name, offset)) => format!("{name}+{offset:#X}"),
+            None => format!("{address:#05X}"),
+        };
+        if let Some((file, line)) = self.location(address) {
+            description.push_str(&format!(" ({file}:{line})"));
+        }
+        description
+    }
+}
+
+// mutators
+impl SymbolTable {
+    pub fn insert_label(&mut self, address: u16, name: impl Into<String>) {
+        self.labels.insert(address, name.into());
+    }
+
+    pub fn insert_line(&mut self, address: u16, file: &str, line: usize) {
+        let file = match self.files.iter().position(|name| name == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(file.to_owned());
+                self.files.len() - 1
+            }
+        };
+        self.lines.insert(address, (file, line));
+    }
+}
+
+// encoding
+impl SymbolTable {
+    /// Writes the table in the text format read by [SymbolTable::parse].
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# chip8 symbols\n");
+        for (address, name) in &self.labels {
+            text.push_str(&format!("label {address:#05X} {name}\n"));
+        }
+        for (address, &(file, line)) in &self.lines {
+            text.push_str(&format!(
+                "line {address:#05X} {line} {}\n",
+                self.files[file]
+            ));
+        }
+        text
+    }
+}
+
+#[test]
+fn test_describe_address() {
+    let mut symbol_table = SymbolTable::new();
+    symbol_table.insert_label(0x200, "main_loop");
+    symbol_table.insert_line(0x204, "game.8o", 37);
+    assert_eq!(symbol_table.describe(0x200), "main_loop");
+    assert_eq!(symbol_table.describe(0x204), "main_loop+0x4 (game.8o:37)");
+    assert_eq!(SymbolTable::new().describe(0x206), "0x206");
+}
+#[test]
+fn test_symbol_text_round_trip() {
+    let text = "# comment\nlabel 0x2A0 sprite\n\nline 0x200 3 my game.8o\n";
+    let symbol_table = SymbolTable::parse(text).unwrap();
+    assert_eq!(symbol_table.location(0x202), Some(("my game.8o", 3)));
+    assert_eq!(
+        SymbolTable::parse(&symbol_table.to_text()),
+        Ok(symbol_table)
+    );
+
+    let error = SymbolTable::parse("label 0x200 main\nline 200 game.8o").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 2: expected a hexadecimal address like 0x200"
+    );
+}
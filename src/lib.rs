@@ -1,10 +1,25 @@
 #![forbid(unsafe_code)]
 #![allow(unused)]
 
+// lets macros refer to this crate as `::chip8` inside of it too
+extern crate self as chip8;
+
+pub mod asm;
+pub mod dap;
+pub mod disasm;
+pub mod gdb;
+pub mod instruction;
 pub mod interpreter;
 pub mod nibbles;
 
 #[cfg(test)]
 pub mod test;
 
-pub use interpreter::Interpreter;
+pub use instruction::{Instruction, InstructionSet};
+pub use interpreter::{
+    AudioGenerator, AudioRecorder, Clock, Display, ExecutionError, Interpreter, Movie, MovieError,
+    Platform, RandomSource, RealTimeClock, Replay, Resolution, RewindBudget, RewindBuffer,
+    RngRandomSource, SaveState, SaveStateError, ScriptedRandomSource, StepOutcome, SymbolError,
+    SymbolTable, VirtualClock, XorShiftRandomSource,
+};
+pub use macros::assert_display_matches;
@@ -27,7 +27,7 @@ pub fn get_second_nibble(value: u8) -> u8 {
 /// # Example
 /// ```
 /// let nibbles = [0xA, 0xB, 0xC];
-/// let combined = chip8::nibbles::combine_three_nibbles(nibbles[0], nibbles[1], nibbles[2]);
+/// let combined = chip8::nibbles::concatenate_three_nibbles(nibbles[0], nibbles[1], nibbles[2]);
 /// assert_eq!(combined, 0x0ABC);
 /// ```
 pub fn concatenate_three_nibbles(first: u8, second: u8, third: u8) -> u16 {
@@ -37,7 +37,7 @@ pub fn concatenate_three_nibbles(first: u8, second: u8, third: u8) -> u16 {
 /// # Example
 /// ```
 /// let nibbles = [0xA, 0xB];
-/// let combined = chip8::nibbles::combine_two_nibbles(nibbles[0], nibbles[1]);
+/// let combined = chip8::nibbles::concatenate_two_nibbles(nibbles[0], nibbles[1]);
 /// assert_eq!(combined, 0xAB);
 /// ```
 pub fn concatenate_two_nibbles(first: u8, second: u8) -> u8 {
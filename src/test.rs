@@ -1,4 +1,4 @@
-use crate::interpreter::{ConfigurationBuilder, Interpreter};
+use crate::interpreter::{ConfigurationBuilder, Interpreter, Resolution};
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
@@ -7,6 +7,8 @@ use crossterm::{
 };
 use std::{io::Write, time::Duration};
 
+mod headless;
+
 fn execute_program_terminal(mut chip8: Interpreter) -> Result<(), std::io::Error> {
     // prepare the terminal
     let mut stdout = std::io::stdout();
@@ -48,13 +50,21 @@ fn execute_program_terminal(mut chip8: Interpreter) -> Result<(), std::io::Error
         }
 
         // print display
-        for (y, row) in chip8.display().iter().enumerate() {
+        for (y, row) in chip8.display().rows().enumerate() {
             stdout.execute(MoveTo(0, y as u16))?;
-            for pixel in row.iter().map(|&pixel| if pixel { "█" } else { " " }) {
+            for pixel in row.iter().map(|&pixel| match pixel {
+                0 => " ",
+                1 => "█",
+                2 => "▒",
+                _ => "▓",
+            }) {
                 stdout.write_all(pixel.as_bytes())?;
             }
         }
-        stdout.execute(MoveTo(0, chip8.configuration().display_height() as u16));
+        stdout.execute(MoveTo(0, chip8.display().height() as u16))?;
+        // the terminal can not play the sound so it is shown instead
+        let sound = if chip8.is_sound_active() { "♪" } else { " " };
+        stdout.write_all(sound.as_bytes())?;
 
         // execute instruction
         if !chip8.execute_current_instruction() {
@@ -85,6 +95,7 @@ fn get_input(prompt: &str) -> Result<String, std::io::Error> {
 }
 
 #[test]
+#[ignore = "interactive"]
 fn user_program() {
     let mut interpreter = Interpreter::default();
 
@@ -0,0 +1,115 @@
+use crate::interpreter::{ExecutionError, Interpreter, Resolution};
+
+/// The number of frames (60hz) every program runs for.
+const FRAME_COUNT: usize = 300;
+const GOLDEN_DIRECTORY: &str = "tests/golden";
+const INPUT_DIRECTORY: &str = "tests/input";
+/// HIRES CHIP-8 programs call COSMAC VIP machine code that is then executed as chip8.
+const HIRES_FAILURE: (usize, ExecutionError) = (
+    3,
+    ExecutionError::UnknownOpcode {
+        program_counter: 0x2AC,
+        opcode: 0xF800,
+    },
+);
+/// Programs that stop with an [ExecutionError], with the frame and the error they stop with.
+/// Their tests check that they still fail the same way.
+const KNOWN_FAILURES: &[(&str, (usize, ExecutionError))] = &[
+    ("astro_dodge_hires", HIRES_FAILURE),
+    ("hires_maze", HIRES_FAILURE),
+    ("hires_particle_demo", HIRES_FAILURE),
+    ("hires_s_triangle", HIRES_FAILURE),
+    ("hires_stars", HIRES_FAILURE),
+    ("hires_test", HIRES_FAILURE),
+    ("hires_worm_v4", HIRES_FAILURE),
+    ("trip_8_hires_demo", HIRES_FAILURE),
+];
+
+/// Reads `tests/input/<name>.txt`. A program without an input script never presses a key.
+///
+/// Each line is `<frame> <keys>`: from that frame on the hexadecimal `keys` are held down
+/// until the next line. `-` releases every key. Lines starting with `#` are comments.
+/// ```text
+/// # hold 5 and 6 for a second
+/// 120 56
+/// 180 -
+/// ```
+fn read_input_script(name: &str) -> Vec<(usize, [bool; 16])> {
+    let path = format!("{INPUT_DIRECTORY}/{name}.txt");
+    let Ok(script) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+
+    script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (frame, keys) = line
+                .split_once(' ')
+                .unwrap_or_else(|| panic!("{path}: expected `<frame> <keys>` in {line:?}"));
+            let frame = frame
+                .parse()
+                .unwrap_or_else(|_| panic!("{path}: bad frame in {line:?}"));
+            let mut keypad = [false; 16];
+            for key in keys.trim().chars().filter(|&key| key != '-') {
+                let key = key
+                    .to_digit(16)
+                    .unwrap_or_else(|| panic!("{path}: bad key in {line:?}"));
+                keypad[key as usize] = true;
+            }
+            (frame, keypad)
+        })
+        .collect()
+}
+
+/// Runs a program for [FRAME_COUNT] frames with its input script.
+/// Returns the frame and the error if an instruction fails.
+///
+/// # Panics
+/// If the program can not be loaded
+fn run_headless(
+    mut interpreter: Interpreter,
+    name: &str,
+    path: &str,
+) -> Result<Interpreter, (usize, ExecutionError)> {
+    interpreter.load_program_from_path(path).unwrap();
+    let input_script = read_input_script(name);
+
+    for frame in 0..FRAME_COUNT {
+        let keypad = input_script
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= frame)
+            .map(|(_, keypad)| *keypad)
+            .unwrap_or_default();
+        *interpreter.keypad_mut() = keypad;
+
+        interpreter.run_frame().map_err(|error| (frame, error))?;
+    }
+    Ok(interpreter)
+}
+
+/// Compares the display of a program after [FRAME_COUNT] frames with `tests/golden/<name>.txt`.
+fn assert_program_matches_golden(interpreter: Interpreter, name: &str, path: &str) {
+    let result = run_headless(interpreter, name, path);
+    if let Some((_, expected)) = KNOWN_FAILURES.iter().find(|(known, _)| *known == name) {
+        assert_eq!(
+            result.err(),
+            Some(*expected),
+            "{name} no longer fails as expected. Update KNOWN_FAILURES"
+        );
+        return;
+    }
+    let interpreter = match result {
+        Ok(interpreter) => interpreter,
+        Err((frame, error)) => panic!("{name} failed on frame {frame}: {error}"),
+    };
+
+    crate::assert_display_matches!(
+        interpreter.display(),
+        format!("{GOLDEN_DIRECTORY}/{name}.txt")
+    );
+}
+
+macros::generate_headless_tests!();